@@ -0,0 +1,89 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::LogLevel;
+use alloc::string::String;
+use core::str;
+use efi::EfiError;
+use efi::EfiGuid;
+use efi::EfiResult;
+use efi::EfiRuntimeServices;
+
+// NVRAM variables owned by as-boot:
+//   AsBootCount  u64 (little endian), incremented on every boot
+//   AsBootOnce   entry title (UTF-8), booted without a menu on the next boot and then deleted
+pub const VENDOR_GUID: EfiGuid = EfiGuid(
+    0x3f1c8a52,
+    0x7d0e,
+    0x4b9a,
+    [0xa5, 0x73, 0x2d, 0x6f, 0x73, 0x62, 0x6f, 0x6f],
+);
+
+const BOOT_COUNT: &str = "AsBootCount";
+const BOOT_ONCE: &str = "AsBootOnce";
+const BOOT_ONCE_MAX: usize = 256;
+const ATTRIBUTES: u32 = EfiRuntimeServices::VARIABLE_NON_VOLATILE
+    | EfiRuntimeServices::VARIABLE_BOOTSERVICE_ACCESS
+    | EfiRuntimeServices::VARIABLE_RUNTIME_ACCESS;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SecureBootState {
+    pub secure_boot: bool,
+    pub setup_mode: bool,
+}
+
+pub fn bump_boot_count() -> EfiResult<u64> {
+    let mut buff = [0u8; 8];
+    let count = match efi_wrapper::get_variable(BOOT_COUNT, &VENDOR_GUID, &mut buff) {
+        Ok((size, _)) if size == buff.len() => u64::from_le_bytes(buff),
+        // missing or malformed, start over
+        Ok(_) | Err(EfiError::NotFound) | Err(EfiError::BufferTooSmall) => 0,
+        Err(error) => return Err(error),
+    }
+    .wrapping_add(1);
+
+    efi_wrapper::set_variable(BOOT_COUNT, &VENDOR_GUID, ATTRIBUTES, &count.to_le_bytes())?;
+    Ok(count)
+}
+
+pub fn take_boot_once() -> Option<String> {
+    let mut buff = [0u8; BOOT_ONCE_MAX];
+    let (size, _) = efi_wrapper::get_variable(BOOT_ONCE, &VENDOR_GUID, &mut buff).ok()?;
+
+    if let Err(error) = efi_wrapper::delete_variable(BOOT_ONCE, &VENDOR_GUID) {
+        log!(
+            LogLevel::Warn,
+            "NVRAM: failed to delete {}: {}",
+            BOOT_ONCE,
+            error
+        );
+    }
+
+    let title = str::from_utf8(&buff[..size]).ok()?;
+    Some(String::from(title))
+}
+
+// missing variables mean the firmware does not support secure boot
+pub fn secure_boot_state() -> SecureBootState {
+    SecureBootState {
+        secure_boot: read_global_flag("SecureBoot"),
+        setup_mode: read_global_flag("SetupMode"),
+    }
+}
+
+pub fn log_variables() {
+    for variable in efi_wrapper::variables() {
+        log!(
+            LogLevel::Debug,
+            "NVRAM: {} {:x?}",
+            &*variable.name,
+            variable.vendor
+        );
+    }
+}
+
+fn read_global_flag(name: &str) -> bool {
+    let mut buff = [0u8; 1];
+    match efi_wrapper::get_variable(name, &EfiRuntimeServices::GLOBAL_VARIABLE_GUID, &mut buff) {
+        Ok((1, _)) => buff[0] == 1,
+        _ => false,
+    }
+}
@@ -0,0 +1,459 @@
+use crate::efi_wrapper::File;
+use crate::efi_wrapper::LogLevel;
+use crate::verify;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use bootcrypto::Sha256;
+use core::str;
+
+// boot.cfg on the ESP: one `key = value` per line, `#` starts a comment.
+// Global keys come first, each `[title]` section starts a boot menu entry.
+// Entry keys before the first section belong to an implicit entry.
+// Paths are on the boot volume unless prefixed with `fsN:` (see efi_wrapper::volumes).
+//
+//   timeout = 3
+//   log = info
+//   log_file = \as-boot.log
+//   resolution = 1280x720
+//   default = as-os
+//
+//   [as-os]
+//   kernel = \kernel.elf
+//   cmdline = console=fb
+//   module = \initrd.tar
+//   module = fs1:\data.img     # needs data.img.sig too when as-boot is built with `sign`
+//   sha256 = 3a7bd3e2...   # optional, the kernel image has to match this digest
+//
+//   [UEFI shell]
+//   efi = \EFI\tools\shell.efi   # chainloaded instead of a kernel, cmdline becomes its load options
+//                                # needs shell.efi.sig when as-boot is built with `sign`
+//   cmdline = -nostartup
+#[derive(Clone, Debug)]
+pub struct Config {
+    entries: Vec<Entry>,
+    // title -> index of the first entry with that title
+    titles: BTreeMap<String, usize>,
+    pub default: usize,
+    pub resolution: Option<(usize, usize)>,
+    pub timeout: usize,
+    pub log_level: LogLevel,
+    pub log_file: Option<String>,
+    // problems found by `parse`, printed by `load`
+    pub diagnostics: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub title: String,
+    pub kernel: String,
+    pub cmdline: String,
+    pub modules: Vec<String>,
+    pub sha256: Option<[u8; Sha256::DIGEST_SIZE]>,
+    // EFI application to chainload, `kernel` and `modules` are ignored if set
+    pub efi: Option<String>,
+    kernel_found: bool,
+}
+
+impl Entry {
+    const DEFAULT_TITLE: &'static str = "as-os";
+    const DEFAULT_KERNEL: &'static str = "kernel.elf";
+
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: String::from(title),
+            kernel: String::from(Self::DEFAULT_KERNEL),
+            cmdline: String::new(),
+            modules: Vec::new(),
+            sha256: None,
+            efi: None,
+            kernel_found: false,
+        }
+    }
+}
+
+impl Config {
+    pub const PATH: &'static str = "\\boot.cfg";
+
+    const DEFAULT_TIMEOUT: usize = 3;
+
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            titles: BTreeMap::new(),
+            default: 0,
+            resolution: None,
+            timeout: Self::DEFAULT_TIMEOUT,
+            log_level: LogLevel::Info,
+            log_file: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    pub fn load() -> Self {
+        let text = match File::load(Self::PATH) {
+            Ok((page_box, load_size)) => match str::from_utf8(&page_box[..load_size]) {
+                Ok(text) => String::from(text),
+                Err(_) => {
+                    println!("CONFIG: {} is not valid UTF-8, using defaults", Self::PATH);
+                    String::new()
+                }
+            },
+            Err(msg) => {
+                println!(
+                    "CONFIG: failed to load {}: {}, using defaults",
+                    Self::PATH,
+                    msg
+                );
+                String::new()
+            }
+        };
+
+        let config = Self::parse(&text);
+        for diagnostic in &config.diagnostics {
+            println!("CONFIG: {}", diagnostic);
+        }
+        config
+    }
+
+    // never fails, problems are collected in `diagnostics` and the defaults are used instead
+    pub fn parse(text: &str) -> Self {
+        let mut config = Self::new();
+        config.parse_lines(text);
+
+        if config.entries.is_empty() {
+            config.push_entry(0, Entry::DEFAULT_TITLE);
+        }
+        for entry in &config.entries {
+            if entry.efi.is_none() && !entry.kernel_found {
+                let diagnostic = format!(
+                    "entry `{}` does not set `kernel`, using {}",
+                    entry.title,
+                    Entry::DEFAULT_KERNEL
+                );
+                config.diagnostics.push(diagnostic);
+            }
+        }
+        if config.entries.len() <= config.default {
+            let diagnostic = format!("default entry {} does not exist, using 0", config.default);
+            config.diagnostics.push(diagnostic);
+            config.default = 0;
+        }
+
+        config
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn find_entry(&self, title: &str) -> Option<usize> {
+        self.titles.get(title).copied()
+    }
+
+    fn parse_lines(&mut self, text: &str) {
+        let mut current_entry: Option<usize> = None;
+        let mut default_entry: Option<(usize, &str)> = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(title) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                current_entry = Some(self.push_entry(line_number, title.trim()));
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                self.diagnostic(line_number, "expected `key = value` or `[title]`", line);
+                continue;
+            };
+            let key = key.trim();
+            let value = Self::unquote(value.trim());
+
+            match key {
+                "kernel" | "cmdline" | "module" | "sha256" | "efi" => {
+                    let entry_index = *current_entry
+                        .get_or_insert_with(|| self.push_entry(line_number, Entry::DEFAULT_TITLE));
+                    self.set_entry_key(entry_index, line_number, key, value);
+                }
+                "default" => default_entry = Some((line_number, value)),
+                "resolution" => match Self::parse_resolution(value) {
+                    Some(resolution) => self.resolution = Some(resolution),
+                    None => self.diagnostic(
+                        line_number,
+                        "invalid resolution (expected WIDTHxHEIGHT)",
+                        value,
+                    ),
+                },
+                "timeout" => match value.parse() {
+                    Ok(timeout) => self.timeout = timeout,
+                    Err(_) => {
+                        self.diagnostic(line_number, "invalid timeout (expected seconds)", value)
+                    }
+                },
+                "log" => match LogLevel::from_str(value) {
+                    Some(log_level) => self.log_level = log_level,
+                    None => self.diagnostic(
+                        line_number,
+                        "invalid log level (expected error, warn, info or debug)",
+                        value,
+                    ),
+                },
+                "log_file" => self.log_file = Some(String::from(value)),
+                _ => self.diagnostic(line_number, "unknown key", key),
+            }
+        }
+
+        if let Some((line_number, value)) = default_entry {
+            match self.find_entry(value).or_else(|| value.parse().ok()) {
+                Some(default) => self.default = default,
+                None => self.diagnostic(line_number, "unknown default entry", value),
+            }
+        }
+    }
+
+    fn push_entry(&mut self, line_number: usize, title: &str) -> usize {
+        let index = self.entries.len();
+        if self.titles.contains_key(title) {
+            self.diagnostic(line_number, "duplicate entry title", title);
+        } else {
+            self.titles.insert(String::from(title), index);
+        }
+
+        self.entries.push(Entry::new(title));
+        index
+    }
+
+    fn set_entry_key(&mut self, entry_index: usize, line_number: usize, key: &str, value: &str) {
+        let entry = &mut self.entries[entry_index];
+
+        match key {
+            "kernel" => {
+                entry.kernel = String::from(value);
+                entry.kernel_found = true;
+            }
+            "cmdline" => entry.cmdline = String::from(value),
+            "module" => entry.modules.push(String::from(value)),
+            "efi" => entry.efi = Some(String::from(value)),
+            "sha256" => match verify::parse_hex(value) {
+                Some(digest) => entry.sha256 = Some(digest),
+                None => self.diagnostic(
+                    line_number,
+                    "invalid sha256 (expected 64 hex digits)",
+                    value,
+                ),
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn parse_resolution(value: &str) -> Option<(usize, usize)> {
+        let (width, height) = value.split_once('x')?;
+        let width = width.trim().parse().ok()?;
+        let height = height.trim().parse().ok()?;
+        if width == 0 || height == 0 {
+            None
+        } else {
+            Some((width, height))
+        }
+    }
+
+    fn unquote(value: &str) -> &str {
+        value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value)
+    }
+
+    fn diagnostic(&mut self, line_number: usize, msg: &str, context: &str) {
+        let diagnostic = format!("{}:{}: {}: `{}`", Self::PATH, line_number, msg, context);
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_file_has_one_default_entry() {
+        let config = Config::parse("");
+        assert_eq!(config.entries().len(), 1);
+        assert_eq!(config.entries()[0].title, "as-os");
+        assert_eq!(config.entries()[0].kernel, "kernel.elf");
+        assert_eq!(config.default, 0);
+        assert_eq!(config.timeout, 3);
+        // the implicit entry does not set `kernel` either
+        assert_eq!(config.diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn sections_start_entries() {
+        let config = Config::parse(
+            "timeout = 5\n\
+             log = debug\n\
+             log_file = \\as-boot.log\n\
+             resolution = 1280x720\n\
+             \n\
+             [as-os]\n\
+             kernel = \\kernel.elf\n\
+             cmdline = console=fb\n\
+             module = \\initrd.tar\n\
+             module = fs1:\\data.img\n\
+             \n\
+             [ UEFI shell ]\n\
+             efi = \\EFI\\tools\\shell.efi\n\
+             cmdline = -nostartup\n",
+        );
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+        assert_eq!(config.timeout, 5);
+        assert_eq!(config.log_level, LogLevel::Debug);
+        assert_eq!(config.log_file.as_deref(), Some("\\as-boot.log"));
+        assert_eq!(config.resolution, Some((1280, 720)));
+
+        let entries = config.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "as-os");
+        assert_eq!(entries[0].kernel, "\\kernel.elf");
+        assert_eq!(entries[0].cmdline, "console=fb");
+        assert_eq!(entries[0].modules, ["\\initrd.tar", "fs1:\\data.img"]);
+        assert_eq!(entries[0].efi, None);
+        assert_eq!(entries[1].title, "UEFI shell");
+        assert_eq!(entries[1].efi.as_deref(), Some("\\EFI\\tools\\shell.efi"));
+        assert_eq!(entries[1].cmdline, "-nostartup");
+        assert_eq!(config.find_entry("UEFI shell"), Some(1));
+        assert_eq!(config.find_entry("missing"), None);
+    }
+
+    #[test]
+    fn entry_keys_before_a_section_make_an_implicit_entry() {
+        let config = Config::parse(
+            "kernel = \\a.elf\n\
+             cmdline = quiet\n\
+             [b]\n\
+             kernel = \\b.elf\n",
+        );
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+        let entries = config.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].title, "as-os");
+        assert_eq!(entries[0].kernel, "\\a.elf");
+        assert_eq!(entries[0].cmdline, "quiet");
+        assert_eq!(entries[1].title, "b");
+    }
+
+    #[test]
+    fn duplicate_titles_keep_the_first() {
+        let config = Config::parse(
+            "[a]\n\
+             kernel = \\first.elf\n\
+             [a]\n\
+             kernel = \\second.elf\n",
+        );
+        assert_eq!(config.entries().len(), 2);
+        assert_eq!(config.find_entry("a"), Some(0));
+        assert_eq!(config.diagnostics.len(), 1);
+        assert!(config.diagnostics[0].contains("duplicate entry title"));
+    }
+
+    #[test]
+    fn default_by_title_or_index() {
+        let text = "[a]\nkernel = a\n[b]\nkernel = b\n[2]\nkernel = c\n";
+
+        let config = Config::parse(&format!("default = b\n{}", text));
+        assert_eq!(config.default, 1);
+        // a title wins over an index
+        let config = Config::parse(&format!("default = 2\n{}", text));
+        assert_eq!(config.default, 2);
+        let config = Config::parse(&format!("default = 0\n{}", text));
+        assert_eq!(config.default, 0);
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+
+        let config = Config::parse(&format!("default = c\n{}", text));
+        assert_eq!(config.default, 0);
+        assert_eq!(config.diagnostics.len(), 1);
+        assert!(config.diagnostics[0].contains("unknown default entry"));
+
+        let config = Config::parse(&format!("default = 7\n{}", text));
+        assert_eq!(config.default, 0);
+        assert_eq!(config.diagnostics.len(), 1);
+        assert!(config.diagnostics[0].contains("default entry 7 does not exist"));
+    }
+
+    #[test]
+    fn default_may_come_before_its_entry() {
+        let config = Config::parse("[a]\nkernel = a\ndefault = b\n[b]\nkernel = b\n");
+        assert_eq!(config.default, 1);
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+    }
+
+    #[test]
+    fn quotes_and_comments() {
+        let config = Config::parse(
+            "# a comment line\n\
+             [a]   # after a title\n\
+             kernel = \"\\a b.elf\"  # after a value\n\
+             cmdline = \"  spaced  \"\n\
+             module = \"unterminated\n",
+        );
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+        let entry = &config.entries()[0];
+        assert_eq!(entry.title, "a");
+        assert_eq!(entry.kernel, "\\a b.elf");
+        assert_eq!(entry.cmdline, "  spaced  ");
+        assert_eq!(entry.modules, ["\"unterminated"]);
+    }
+
+    #[test]
+    fn sha256_is_parsed() {
+        let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let config = Config::parse(&format!("[a]\nkernel = a\nsha256 = {}\n", digest));
+        assert!(config.diagnostics.is_empty(), "{:?}", config.diagnostics);
+        let sha256 = config.entries()[0].sha256.unwrap();
+        assert_eq!(sha256[..4], [0xba, 0x78, 0x16, 0xbf]);
+        assert_eq!(sha256[31], 0xad);
+
+        let signed = format!("+a{}", &digest[2..]);
+        for value in ["abcd", &digest[1..], &signed] {
+            let config = Config::parse(&format!("[a]\nkernel = a\nsha256 = {}\n", value));
+            assert_eq!(config.entries()[0].sha256, None, "{}", value);
+            assert_eq!(config.diagnostics.len(), 1, "{}", value);
+        }
+    }
+
+    #[test]
+    fn every_problem_is_one_diagnostic() {
+        let config = Config::parse(
+            "timeout = soon\n\
+             log = verbose\n\
+             resolution = 1280x0\n\
+             colour = red\n\
+             no equals sign\n\
+             [a]\n\
+             cmdline = quiet\n\
+             [efi]\n\
+             efi = \\shell.efi\n",
+        );
+        // five bad lines, and entry `a` does not set `kernel`
+        assert_eq!(config.diagnostics.len(), 6, "{:?}", config.diagnostics);
+        assert!(config.diagnostics[0].starts_with("\\boot.cfg:1: invalid timeout"));
+        assert!(config.diagnostics[4].starts_with("\\boot.cfg:5: expected `key = value`"));
+        assert!(config.diagnostics[5].contains("entry `a` does not set `kernel`"));
+        // the defaults stay in place
+        assert_eq!(config.timeout, 3);
+        assert_eq!(config.log_level, LogLevel::Info);
+        assert_eq!(config.resolution, None);
+        assert_eq!(config.entries()[0].kernel, "kernel.elf");
+    }
+}
@@ -0,0 +1,68 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::LogLevel;
+use crate::efi_wrapper::PageBox;
+use crate::error::BootResult;
+use crate::error::Context;
+use bootinfo::BootSlice;
+use bootinfo::CpuInfo;
+use core::slice;
+use efi::EfiProcessorInformation;
+
+// Asks EFI_MP_SERVICES_PROTOCOL for every logical processor and keeps the list after
+// exit_boot_services. The BSP is the processor as-boot runs on.
+pub fn discover() -> BootResult<BootSlice<CpuInfo>> {
+    let (count, enabled_count) =
+        efi_wrapper::processor_count().context("failed to locate MP services")?;
+    log!(
+        LogLevel::Info,
+        "CPU: {} processors, {} enabled",
+        count,
+        enabled_count
+    );
+    if count == 0 {
+        return Ok(BootSlice::empty());
+    }
+
+    let buff: &'static mut [u8] = PageBox::new_from_bytes(count * size_of::<CpuInfo>()).leak();
+    let cpus = unsafe { slice::from_raw_parts_mut(buff.as_mut_ptr() as *mut CpuInfo, count) };
+
+    for (processor_number, cpu) in cpus.iter_mut().enumerate() {
+        let info = efi_wrapper::processor_info(processor_number)
+            .context("failed to get processor information")?;
+
+        *cpu = CpuInfo {
+            apic_id: info.processor_id as u32,
+            package: info.location.package,
+            core: info.location.core,
+            thread: info.location.thread,
+            flags: cpu_flags(info.status_flag),
+        };
+        log!(
+            LogLevel::Info,
+            "CPU: #{} APIC id {}, package {} core {} thread {}{}{}",
+            processor_number,
+            cpu.apic_id,
+            cpu.package,
+            cpu.core,
+            cpu.thread,
+            if cpu.is_bsp() { " (BSP)" } else { "" },
+            if cpu.is_enabled() { "" } else { " (disabled)" }
+        );
+    }
+
+    Ok(BootSlice::from_slice(cpus))
+}
+
+fn cpu_flags(status_flag: u32) -> u32 {
+    let mut flags = 0;
+    if status_flag & EfiProcessorInformation::PROCESSOR_AS_BSP_BIT != 0 {
+        flags |= CpuInfo::BSP;
+    }
+    if status_flag & EfiProcessorInformation::PROCESSOR_ENABLED_BIT != 0 {
+        flags |= CpuInfo::ENABLED;
+    }
+    if status_flag & EfiProcessorInformation::PROCESSOR_HEALTH_STATUS_BIT != 0 {
+        flags |= CpuInfo::HEALTHY;
+    }
+    flags
+}
@@ -1,7 +1,20 @@
+// host unit tests run on the allocator and panic handler of std
+#[cfg(not(test))]
+mod allocator;
+mod file;
+mod image;
+#[cfg(not(test))]
+mod panic;
 pub mod static_str;
 
+pub use file::*;
+pub use image::*;
+
 use efi::*;
 
+use crate::serial::SerialPort;
+use alloc::vec;
+use alloc::vec::Vec;
 use bootgfx::FrameBuffer;
 use bootgfx::FrameBufferMode;
 use bootgfx::terminal::Terminal;
@@ -9,17 +22,57 @@ use core::ffi::c_void;
 use core::fmt;
 use core::fmt::Write;
 use core::mem;
-use core::mem::MaybeUninit;
 use core::ops::Deref;
 use core::ops::DerefMut;
-use core::panic::PanicInfo;
 use core::ptr;
 use core::slice;
+use static_str::StaticStr;
 
 static mut SYSTEM_TABLE: *const EfiSystemTable = ptr::null();
 static mut BOOT_SERVICES: *const EfiBootServices = ptr::null();
 static mut IMAGE_HANDLE: Option<EfiHandle> = None;
 static mut TERMINAL: Option<Terminal> = None;
+static mut SERIAL: Option<SerialPort> = None;
+static mut LOG_LEVEL: LogLevel = LogLevel::Info;
+static mut TSC_PER_MS: u64 = 0;
+static mut LOG_BUFFER: [u8; LOG_BUFFER_SIZE] = [0; LOG_BUFFER_SIZE];
+static mut LOG_BUFFER_LEN: usize = 0;
+static mut LOG_FILE: Option<StaticStr<256>> = None;
+// bytes of LOG_BUFFER already in the log file
+static mut LOG_FILE_LEN: usize = 0;
+
+// everything printed before exit_boot_services, written to the log file on request
+const LOG_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            _ => None,
+        }
+    }
+}
+
+pub fn log_level() -> LogLevel {
+    unsafe { LOG_LEVEL }
+}
+
+pub fn set_log_level(level: LogLevel) {
+    unsafe {
+        LOG_LEVEL = level;
+    }
+}
 
 pub fn take_terminal() -> Option<Terminal> {
     unsafe {
@@ -40,60 +93,108 @@ pub fn write_terminal(s: &str) {
     }
 }
 
+pub fn init_serial() {
+    unsafe {
+        SERIAL = SerialPort::init(SerialPort::COM1);
+    }
+}
+
+pub fn write_serial(s: &str) {
+    if let Some(serial) = unsafe { &*(&raw const SERIAL) } {
+        serial.write(s);
+    }
+}
+
+pub fn write_stdout(s: &str) {
+    write_serial(s);
+    write_terminal(s);
+    record_log(s);
+}
+
+// output past LOG_BUFFER_SIZE is dropped
+fn record_log(s: &str) {
+    unsafe {
+        let buffer = &mut *(&raw mut LOG_BUFFER);
+        let len = s.len().min(LOG_BUFFER_SIZE - LOG_BUFFER_LEN);
+        buffer[LOG_BUFFER_LEN..LOG_BUFFER_LEN + len].copy_from_slice(&s.as_bytes()[..len]);
+        LOG_BUFFER_LEN += len;
+    }
+}
+
+pub fn set_log_file(path: &str) {
+    unsafe {
+        LOG_FILE = Some(StaticStr::from(path));
+    }
+}
+
+// writes the log so far to the configured log file, the first call truncates it
+// and later ones (a panic after the regular write) only append what is new
+pub fn write_log_file() -> EfiResult<()> {
+    let Some(path) = (unsafe { &*(&raw const LOG_FILE) }) else {
+        return Ok(());
+    };
+    let log = unsafe { &(&*(&raw const LOG_BUFFER))[..LOG_BUFFER_LEN] };
+    let written = unsafe { LOG_FILE_LEN };
+
+    let mut file = if written == 0 {
+        File::create(path)?
+    } else {
+        File::append(path)?
+    };
+    file.write_all(&log[written..])?;
+    file.flush()?;
+    unsafe {
+        LOG_FILE_LEN = log.len();
+    }
+    Ok(())
+}
+
+pub fn clear_terminal() {
+    if let Some(terminal) = unsafe { &mut *(&raw mut TERMINAL) } {
+        terminal.clean();
+    }
+}
+
+// fmt::Write sink for print!/println!, usable in every phase since it needs no buffer
+pub struct Stdout;
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write_stdout(s);
+        Ok(())
+    }
+}
+
 #[macro_export]
 macro_rules! println {
     ($($arg:tt)*) => {{
-        use crate::efi_wrapper::write_terminal;
-        use core::fmt::Write;
-        let mut buff = crate::efi_wrapper::static_str::StaticStr::<1024>::new();
-        write!(&mut buff, $($arg)*).unwrap();
-        write!(&mut buff, "\n\r").unwrap();
-
-        write_terminal(&*buff);
+        print!($($arg)*);
+        print!("\n\r");
     }};
 }
 
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {{
-        use crate::efi_wrapper::write_terminal;
         use core::fmt::Write;
-        let mut buff = crate::efi_wrapper::static_str::StaticStr::<1024>::new();
-        write!(&mut buff, $($arg)*).unwrap();
-
-        write_terminal(&*buff);
+        let _ = write!(crate::efi_wrapper::Stdout, $($arg)*);
     }};
 }
 
 #[macro_export]
-macro_rules! format {
-    ($($arg:tt)*) => {{
-        use core::fmt::Write;
-        let mut buff = crate::efi_wrapper::static_str::StaticStr::<1024>::new();
-        write!(&mut buff, $($arg)*).unwrap();
-        buff
-    }}
-}
-
-#[panic_handler]
-fn panic(info: &PanicInfo) -> ! {
-    if check_boot_services_is_avaiable().is_ok() {
-        if let Some(location) = info.location() {
-            print!(
-                "paniced at {}:{}:{}: ",
-                location.file(),
-                location.line(),
-                location.column()
-            );
-        } else {
-            print!("paniced: ");
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {{
+        if $level <= crate::efi_wrapper::log_level() {
+            println!($($arg)*);
         }
+    }};
+}
 
-        if let Some(s) = info.message().as_str() {
-            println!("{}", s);
-        }
-    }
-    loop {}
+#[macro_export]
+macro_rules! format {
+    ($($arg:tt)*) => {
+        alloc::format!($($arg)*)
+    };
 }
 
 pub unsafe fn init(image_handle: EfiHandle, system_table: *const EfiSystemTable) {
@@ -104,53 +205,115 @@ pub unsafe fn init(image_handle: EfiHandle, system_table: *const EfiSystemTable)
             BOOT_SERVICES = (&*system_table).boot_services;
         }
     }
+    if check_boot_services_is_avaiable().is_ok() {
+        calibrate_tsc();
+    }
+}
+
+fn rdtsc() -> u64 {
+    unsafe { core::arch::x86_64::_rdtsc() }
+}
+
+// measures the TSC against Stall so that delay_ms keeps working after exit_boot_services
+fn calibrate_tsc() {
+    const CALIBRATION_MS: u64 = 10;
+
+    let stall = unsafe { (*BOOT_SERVICES).stall };
+    let start = rdtsc();
+    unsafe {
+        (stall)(CALIBRATION_MS as UIntN * 1000);
+    }
+    let tsc_per_ms = (rdtsc() - start) / CALIBRATION_MS;
+
+    unsafe {
+        TSC_PER_MS = tsc_per_ms;
+    }
+}
+
+pub fn delay_ms(ms: usize) {
+    if check_boot_services_is_avaiable().is_ok() {
+        unsafe {
+            ((*BOOT_SERVICES).stall)(ms * 1000);
+        }
+        return;
+    }
+
+    let end = rdtsc() + ms as u64 * unsafe { TSC_PER_MS };
+    while rdtsc() < end {
+        core::hint::spin_loop();
+    }
 }
 
-fn check_boot_services_is_avaiable() -> Result<(), &'static str> {
+// boot services are gone after exit_boot_services, so every call into them is unsupported
+fn check_boot_services_is_avaiable() -> EfiResult<()> {
     if unsafe { SYSTEM_TABLE == ptr::null() || BOOT_SERVICES == ptr::null() } {
-        Err("unexpected call of stdout")
+        Err(EfiError::Unsupported)
     } else {
         Ok(())
     }
 }
 
-pub fn alloc_pages(pages: usize) -> *mut u8 {
-    check_boot_services_is_avaiable().expect("use after exit_boot_services");
+fn boot_services() -> EfiResult<&'static EfiBootServices> {
+    check_boot_services_is_avaiable()?;
+    Ok(unsafe { &*BOOT_SERVICES })
+}
+
+pub fn alloc_pages(pages: usize) -> EfiResult<*mut u8> {
+    allocate_pages(EfiAllocateType::AllocateAnyPages, 0, pages)
+}
+
+// fails with NotFound if any page of the range is already in use
+pub fn alloc_pages_at(addr: usize, pages: usize) -> EfiResult<*mut u8> {
+    allocate_pages(EfiAllocateType::AllocateAddress, addr, pages)
+}
+
+// the pages end at or below `max_addr`
+pub fn alloc_pages_below(max_addr: usize, pages: usize) -> EfiResult<*mut u8> {
+    allocate_pages(EfiAllocateType::AllocateMaxAddress, max_addr, pages)
+}
+
+fn allocate_pages(r#type: EfiAllocateType, addr: usize, pages: usize) -> EfiResult<*mut u8> {
+    check_boot_services_is_avaiable()?;
 
     if pages == 0 {
-        ptr::null_mut()
+        Ok(ptr::null_mut())
     } else {
         let allocate_pages = unsafe { (&*BOOT_SERVICES).allocate_pages };
-        let mut memory: EfiPhysicalAddress = 0;
-
-        if unsafe {
-            (allocate_pages)(
-                EfiAllocateType::AllocateAnyPages,
-                EfiMemoryType::EfiLoaderData,
-                pages,
-                &raw mut memory,
-            )
-        } != EFI_STATUS_SUCCESS
-        {
-            panic!("failed to alloc pages");
-        }
+        let mut memory: EfiPhysicalAddress = addr as EfiPhysicalAddress;
+
+        status_to_result(unsafe {
+            (allocate_pages)(r#type, EfiMemoryType::EfiLoaderData, pages, &raw mut memory)
+        })?;
 
         let ptr: *mut u8 = memory as usize as *mut u8;
-        ptr
+        Ok(ptr)
     }
 }
 
-pub fn dealloc_pages(ptr: *mut u8, pages: usize) {
-    check_boot_services_is_avaiable().expect("use after exit_boot_services");
+pub fn dealloc_pages(ptr: *mut u8, pages: usize) -> EfiResult<()> {
+    // after exit_boot_services the memory map is final, so the pages are simply leaked
+    if check_boot_services_is_avaiable().is_err() {
+        return Ok(());
+    }
 
     if pages != 0 {
         let free_pages = unsafe { (&*BOOT_SERVICES).free_pages };
         let memory: EfiPhysicalAddress = ptr.addr() as EfiPhysicalAddress;
 
-        if unsafe { (free_pages)(memory, pages) } != EFI_STATUS_SUCCESS {
-            panic!("failed to free pages");
-        }
+        status_to_result(unsafe { (free_pages)(memory, pages) })?;
     }
+
+    Ok(())
+}
+
+pub fn dealloc_pool(ptr: *mut u8) -> EfiResult<()> {
+    // leaked after exit_boot_services like dealloc_pages
+    if check_boot_services_is_avaiable().is_err() {
+        return Ok(());
+    }
+
+    let free_pool = unsafe { (&*BOOT_SERVICES).free_pool };
+    status_to_result(unsafe { (free_pool)(ptr as *mut c_void) })
 }
 
 #[derive(Debug)]
@@ -163,7 +326,10 @@ impl PageBox {
     pub const PAGE_SIZE: usize = 4096;
 
     pub fn new(pages: usize) -> Self {
-        let page: *mut u8 = alloc_pages(pages);
+        let page: *mut u8 = match alloc_pages(pages) {
+            Ok(page) => page,
+            Err(error) => panic!("failed to alloc {} pages: {}", pages, error),
+        };
 
         Self {
             page: page,
@@ -172,15 +338,40 @@ impl PageBox {
     }
 
     pub fn new_from_bytes(size: usize) -> Self {
-        let alloc_pages = (size + Self::PAGE_SIZE - 1) / Self::PAGE_SIZE;
-        Self::new(alloc_pages)
+        Self::new(Self::pages_for(size))
+    }
+
+    // unlike new, failing is expected here (the range may be taken) and left to the caller
+    pub fn new_at(addr: usize, pages: usize) -> EfiResult<Self> {
+        Ok(Self {
+            page: alloc_pages_at(addr, pages)?,
+            pages: pages,
+        })
+    }
+
+    // for memory that has to be reachable before the CPU is in long mode
+    pub fn new_below(max_addr: usize, pages: usize) -> EfiResult<Self> {
+        Ok(Self {
+            page: alloc_pages_below(max_addr, pages)?,
+            pages: pages,
+        })
+    }
+
+    pub const fn pages_for(size: usize) -> usize {
+        (size + Self::PAGE_SIZE - 1) / Self::PAGE_SIZE
     }
 
     pub fn as_slice(&self) -> &[u8] {
+        if self.pages == 0 {
+            return &[];
+        }
         unsafe { slice::from_raw_parts(self.page, self.pages * Self::PAGE_SIZE) }
     }
 
     pub fn as_slice_mut(&mut self) -> &mut [u8] {
+        if self.pages == 0 {
+            return &mut [];
+        }
         unsafe { slice::from_raw_parts_mut(self.page, self.pages * Self::PAGE_SIZE) }
     }
 
@@ -193,13 +384,20 @@ impl PageBox {
     }
 
     pub fn leak(self) -> &'static mut [u8] {
-        unsafe { slice::from_raw_parts_mut(self.page, self.pages * Self::PAGE_SIZE) }
+        if self.pages == 0 {
+            return &mut [];
+        }
+        let leaked = unsafe { slice::from_raw_parts_mut(self.page, self.pages * Self::PAGE_SIZE) };
+        mem::forget(self);
+        leaked
     }
 }
 
 impl Drop for PageBox {
     fn drop(&mut self) {
-        dealloc_pages(self.page, self.pages);
+        if let Err(error) = dealloc_pages(self.page, self.pages) {
+            panic!("failed to free pages: {}", error);
+        }
     }
 }
 
@@ -217,153 +415,268 @@ impl DerefMut for PageBox {
     }
 }
 
-#[derive(Debug)]
-pub struct File {
-    protocol: *const EfiFileProtocol,
+pub fn reset_input() {
+    check_boot_services_is_avaiable().expect("use after exit_boot_services");
+
+    unsafe {
+        let con_in = (*SYSTEM_TABLE).con_in;
+        ((*con_in).reset)(con_in, false);
+    }
 }
 
-impl Drop for File {
-    fn drop(&mut self) {
-        if check_boot_services_is_avaiable().is_ok() {
-            unsafe {
-                let close = (&*self.protocol).close;
-                (close)(self.protocol);
-            }
+pub fn read_key() -> Option<EfiInputKey> {
+    check_boot_services_is_avaiable().expect("use after exit_boot_services");
+
+    let mut key = EfiInputKey {
+        scan_code: 0,
+        unicode_char: 0,
+    };
+    unsafe {
+        let con_in = (*SYSTEM_TABLE).con_in;
+        if ((*con_in).read_key_stroke)(con_in, &raw mut key) == EFI_STATUS_SUCCESS {
+            Some(key)
+        } else {
+            None
         }
     }
 }
 
-impl File {
-    pub fn new(path: &str) -> Result<Self, &'static str> {
-        check_boot_services_is_avaiable()?;
-        let root = Self::get_root()?;
-        let open = unsafe { (&*root).open };
+// waits for a key stroke, or returns None once `timeout_ms` has elapsed
+pub fn wait_for_key(timeout_ms: Option<usize>) -> Option<EfiInputKey> {
+    check_boot_services_is_avaiable().expect("use after exit_boot_services");
 
-        let path_utf16 = utf16::as_utf16::<1024>(path);
+    let boot_services = unsafe { &*BOOT_SERVICES };
+    let con_in = unsafe { (*SYSTEM_TABLE).con_in };
+    let wait_for_key = unsafe { (*con_in).wait_for_key };
 
-        let mut protocol = ptr::null();
+    let Some(timeout_ms) = timeout_ms else {
+        let mut index: UIntN = 0;
         unsafe {
-            if (open)(
-                root,
-                &raw mut protocol,
-                &raw const path_utf16 as *const _,
-                EfiFileProtocol::EFI_FILE_MODE_READ,
-                0,
-            ) != EFI_STATUS_SUCCESS
-            {
-                return Err("failed to open file");
-            }
+            (boot_services.wait_for_event)(1, &raw const wait_for_key, &raw mut index);
         }
+        return read_key();
+    };
 
-        Ok(Self { protocol: protocol })
+    let mut timer: EfiEvent = ptr::null_mut();
+    if let Err(error) = status_to_result(unsafe {
+        (boot_services.create_event)(
+            EfiBootServices::EVT_TIMER,
+            EfiBootServices::TPL_APPLICATION,
+            None,
+            ptr::null(),
+            &raw mut timer,
+        )
+    }) {
+        panic!("failed to create timer event: {}", error);
     }
 
-    pub fn size(&self) -> usize {
-        check_boot_services_is_avaiable().expect("use after exit_boot_services");
+    // the trigger time is in 100ns units
+    let trigger_time = timeout_ms as UInt64 * 10_000;
+    unsafe {
+        (boot_services.set_timer)(timer, EfiTimerDelay::TimerRelative, trigger_time);
+    }
 
-        let get_info = unsafe { (&*self.protocol).get_info };
+    let events = [wait_for_key, timer];
+    let mut index: UIntN = 0;
+    unsafe {
+        (boot_services.wait_for_event)(events.len(), events.as_ptr(), &raw mut index);
+        (boot_services.close_event)(timer);
+    }
 
-        let file_info_guid = EfiFileInfo::GUID;
-        let mut file_info_buff = [0u8; 1024];
-        let mut file_info_buff_size: UIntN = file_info_buff.len();
+    if index == 0 { read_key() } else { None }
+}
 
-        if unsafe {
-            (get_info)(
-                self.protocol,
-                &raw const file_info_guid,
-                &raw mut file_info_buff_size,
-                &raw mut file_info_buff as _,
-            )
-        } != EFI_STATUS_SUCCESS
-        {
-            panic!("failed to get file size");
-        }
+pub fn configuration_table(guid: &EfiGuid) -> Option<*const c_void> {
+    let system_table = unsafe { SYSTEM_TABLE.as_ref()? };
+    if system_table.configuration_table.is_null() {
+        return None;
+    }
 
-        let mut file_info_maybe_uninit = MaybeUninit::<EfiFileInfo>::uninit();
-        let file_info;
-        unsafe {
-            (&raw const file_info_buff as *const u8).copy_to(
-                file_info_maybe_uninit.as_mut_ptr() as *mut u8,
-                mem::size_of::<EfiFileInfo>(),
-            );
-            file_info = file_info_maybe_uninit.assume_init();
-        }
+    let tables = unsafe {
+        slice::from_raw_parts(
+            system_table.configuration_table,
+            system_table.number_of_table_entries,
+        )
+    };
+    tables
+        .iter()
+        .find(|table| table.vendor_guid == *guid)
+        .map(|table| table.vendor_table)
+}
 
-        file_info.file_size as usize
-    }
+fn runtime_services() -> EfiResult<&'static EfiRuntimeServices> {
+    let system_table = unsafe { SYSTEM_TABLE.as_ref() }.ok_or(EfiError::NotReady)?;
+    Ok(unsafe { &*system_table.runtime_services })
+}
 
-    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, &'static str> {
-        check_boot_services_is_avaiable()?;
+pub const VARIABLE_NAME_MAX: usize = 128;
+
+// reads a variable into `buff` and returns its size and attributes
+pub fn get_variable(name: &str, vendor: &EfiGuid, buff: &mut [u8]) -> EfiResult<(usize, UInt32)> {
+    let runtime_services = runtime_services()?;
+    let name_utf16 = utf16::as_utf16::<VARIABLE_NAME_MAX>(name);
+
+    let mut attributes: UInt32 = 0;
+    let mut data_size: UIntN = buff.len();
+    status_to_result(unsafe {
+        (runtime_services.get_variable)(
+            name_utf16.as_ptr(),
+            vendor,
+            &raw mut attributes,
+            &raw mut data_size,
+            buff.as_mut_ptr() as *mut c_void,
+        )
+    })?;
 
-        let read = unsafe { (&*self.protocol).read };
+    Ok((data_size, attributes))
+}
 
-        let mut buffer_size: UIntN = buf.len();
-        let buffer: *mut u8 = buf as *mut _ as *mut u8;
-        if unsafe { (read)(self.protocol, &raw mut buffer_size, buffer) } != EFI_STATUS_SUCCESS {
-            return Err("failed to read to buffer");
-        }
+pub fn set_variable(
+    name: &str,
+    vendor: &EfiGuid,
+    attributes: UInt32,
+    data: &[u8],
+) -> EfiResult<()> {
+    let runtime_services = runtime_services()?;
+    let name_utf16 = utf16::as_utf16::<VARIABLE_NAME_MAX>(name);
+
+    status_to_result(unsafe {
+        (runtime_services.set_variable)(
+            name_utf16.as_ptr(),
+            vendor,
+            attributes,
+            data.len(),
+            data.as_ptr() as *const c_void,
+        )
+    })
+}
 
-        Ok(buffer_size)
-    }
+// writing zero bytes with no attributes deletes the variable
+pub fn delete_variable(name: &str, vendor: &EfiGuid) -> EfiResult<()> {
+    set_variable(name, vendor, 0, &[])
+}
 
-    fn get_root() -> Result<*const EfiFileProtocol, &'static str> {
-        static mut ROOT: *const EfiFileProtocol = ptr::null();
+#[derive(Clone, Copy, Debug)]
+pub struct Variable {
+    pub name: StaticStr<VARIABLE_NAME_MAX>,
+    pub vendor: EfiGuid,
+}
 
-        if unsafe { ROOT.is_null() } {
-            let volume = Self::get_volume()?;
-            unsafe {
-                let open_volume = (&*volume).open_volume;
-                if (open_volume)(volume, &raw mut ROOT) != EFI_STATUS_SUCCESS {
-                    ROOT = ptr::null();
-                    return Err("failed to open volume");
-                }
+#[derive(Clone, Debug)]
+pub struct VariableIter {
+    // grows when the firmware reports a longer name
+    name: Vec<Char16>,
+    vendor: EfiGuid,
+    done: bool,
+}
+
+impl Iterator for VariableIter {
+    type Item = Variable;
+
+    // names longer than VARIABLE_NAME_MAX are truncated in the returned Variable
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let Ok(runtime_services) = runtime_services() else {
+            self.done = true;
+            return None;
+        };
+
+        let mut name_size: UIntN = mem::size_of_val(self.name.as_slice());
+        let mut result = status_to_result(unsafe {
+            (runtime_services.get_next_variable_name)(
+                &raw mut name_size,
+                self.name.as_mut_ptr(),
+                &raw mut self.vendor,
+            )
+        });
+        if result == Err(EfiError::BufferTooSmall) {
+            // name_size is now the size the firmware needs, the previous name is kept
+            self.name.resize(name_size.div_ceil(size_of::<Char16>()), 0);
+            name_size = mem::size_of_val(self.name.as_slice());
+            result = status_to_result(unsafe {
+                (runtime_services.get_next_variable_name)(
+                    &raw mut name_size,
+                    self.name.as_mut_ptr(),
+                    &raw mut self.vendor,
+                )
+            });
+        }
+        if result.is_err() {
+            self.done = true;
+            return None;
+        }
+
+        let mut name = StaticStr::new();
+        let name_len = self
+            .name
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(self.name.len());
+        for c in char::decode_utf16(self.name[..name_len].iter().copied()) {
+            if name
+                .write_char(c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .is_err()
+            {
+                break;
             }
         }
 
-        unsafe { Ok(ROOT) }
+        Some(Variable {
+            name: name,
+            vendor: self.vendor,
+        })
     }
+}
 
-    fn get_volume() -> Result<*const EfiSimpleFileSystemProtocol, &'static str> {
-        check_boot_services_is_avaiable()?;
+pub fn variables() -> VariableIter {
+    VariableIter {
+        name: vec![0; VARIABLE_NAME_MAX],
+        vendor: EfiGuid(0, 0, 0, [0; 8]),
+        done: false,
+    }
+}
 
-        let locate_protocol = unsafe { (&*BOOT_SERVICES).locate_protocol };
+// fills `buff` from EFI_RNG_PROTOCOL with the firmware's default algorithm
+pub fn get_random(buff: &mut [u8]) -> EfiResult<()> {
+    let rng = boot_services()?.locate::<EfiRngProtocol>()?;
+    status_to_result(unsafe { (rng.get_rng)(rng, ptr::null(), buff.len(), buff.as_mut_ptr()) })
+}
 
-        let efi_simple_file_system_protocol_guid = EfiSimpleFileSystemProtocol::GUID;
-        let mut efi_simple_file_system_protocol: *const EfiSimpleFileSystemProtocol =
-            ptr::null_mut();
-        if unsafe {
-            (locate_protocol)(
-                &raw const efi_simple_file_system_protocol_guid,
-                ptr::null(),
-                &raw mut efi_simple_file_system_protocol as *mut *const c_void,
-            )
-        } == EFI_STATUS_SUCCESS
-        {
-            Ok(efi_simple_file_system_protocol)
-        } else {
-            Err("failed to locate EfiSimpleFileSystemProtocol")
-        }
-    }
+// (all processors, enabled processors)
+pub fn processor_count() -> EfiResult<(usize, usize)> {
+    let mp_services = boot_services()?.locate::<EfiMpServicesProtocol>()?;
+    let mut count: UIntN = 0;
+    let mut enabled_count: UIntN = 0;
+    status_to_result(unsafe {
+        (mp_services.get_number_of_processors)(mp_services, &raw mut count, &raw mut enabled_count)
+    })?;
+    Ok((count, enabled_count))
 }
 
-pub fn get_frame_buffer() -> Result<FrameBuffer, &'static str> {
-    check_boot_services_is_avaiable()?;
-    let locate_protocol = unsafe { (*BOOT_SERVICES).locate_protocol };
+pub fn processor_info(processor_number: usize) -> EfiResult<EfiProcessorInformation> {
+    let mp_services = boot_services()?.locate::<EfiMpServicesProtocol>()?;
+    let mut info = EfiProcessorInformation {
+        processor_id: 0,
+        status_flag: 0,
+        location: EfiCpuPhysicalLocation::default(),
+        extended_information: EfiCpuPhysicalLocation2::default(),
+    };
+    status_to_result(unsafe {
+        (mp_services.get_processor_info)(mp_services, processor_number, &raw mut info)
+    })?;
+    Ok(info)
+}
 
-    let efi_graphics_output_protocol_guid = EfiGraphicsOutputProtocol::GUID;
-    let mut efi_graphics_output_protocol: *const EfiGraphicsOutputProtocol = ptr::null();
-    if unsafe {
-        (locate_protocol)(
-            &raw const efi_graphics_output_protocol_guid,
-            ptr::null(),
-            &raw mut efi_graphics_output_protocol as *mut _,
-        )
-    } != EFI_STATUS_SUCCESS
-    {
-        return Err("failed to locate EfiGraphicsOutputProtocol");
-    }
+fn get_graphics_output_protocol() -> EfiResult<&'static EfiGraphicsOutputProtocol> {
+    boot_services()?.locate::<EfiGraphicsOutputProtocol>()
+}
 
-    let efi_graphics_output_protocol_mode = unsafe { *(*efi_graphics_output_protocol).mode };
+pub fn get_frame_buffer() -> EfiResult<FrameBuffer> {
+    let efi_graphics_output_protocol = get_graphics_output_protocol()?;
+
+    let efi_graphics_output_protocol_mode = unsafe { *efi_graphics_output_protocol.mode };
     let efi_graphics_output_mode_information = unsafe { *efi_graphics_output_protocol_mode.info };
 
     let mode = match efi_graphics_output_mode_information.pixel_format {
@@ -385,11 +698,100 @@ pub fn get_frame_buffer() -> Result<FrameBuffer, &'static str> {
     ))
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GraphicsMode {
+    pub number: UInt32,
+    pub width: usize,
+    pub height: usize,
+    pub stride: usize,
+    pub pixel_format: EfiGraphicsPixelFormat,
+}
+
+impl GraphicsMode {
+    fn from_info(number: UInt32, info: &EfiGraphicsOutputModeInformation) -> Self {
+        Self {
+            number: number,
+            width: info.horizontal_resolution as usize,
+            height: info.vertical_resolution as usize,
+            stride: info.pixels_per_scanline as usize,
+            pixel_format: info.pixel_format,
+        }
+    }
+
+    // bootgfx can only draw to 32bit RGB/BGR frame buffers
+    pub fn is_drawable(&self) -> bool {
+        self.pixel_format == EfiGraphicsPixelFormat::PixelRedGreenBlueReserved8BitPerColor
+            || self.pixel_format == EfiGraphicsPixelFormat::PixelBlueGreenRedReserved8BitPerColor
+    }
+
+    pub fn pixels(&self) -> usize {
+        self.width * self.height
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GraphicsModeIter {
+    protocol: &'static EfiGraphicsOutputProtocol,
+    number: UInt32,
+    max_mode: UInt32,
+}
+
+impl Iterator for GraphicsModeIter {
+    type Item = GraphicsMode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        check_boot_services_is_avaiable().expect("use after exit_boot_services");
+
+        while self.number < self.max_mode {
+            let number = self.number;
+            self.number += 1;
+
+            let query_mode = self.protocol.query_mode;
+            let mut size_of_info: UIntN = 0;
+            let mut info: *const EfiGraphicsOutputModeInformation = ptr::null();
+            if unsafe { (query_mode)(self.protocol, number, &raw mut size_of_info, &raw mut info) }
+                == EFI_STATUS_SUCCESS
+            {
+                let mode = GraphicsMode::from_info(number, unsafe { &*info });
+                // the mode information is allocated from pool for the caller
+                let _ = dealloc_pool(info as *mut u8);
+                return Some(mode);
+            }
+        }
+
+        None
+    }
+}
+
+pub fn graphics_modes() -> EfiResult<GraphicsModeIter> {
+    let protocol = get_graphics_output_protocol()?;
+    let max_mode = unsafe { (*protocol.mode).max_mode };
+
+    Ok(GraphicsModeIter {
+        protocol: protocol,
+        number: 0,
+        max_mode: max_mode,
+    })
+}
+
+pub fn current_graphics_mode() -> EfiResult<GraphicsMode> {
+    let protocol = get_graphics_output_protocol()?;
+    let mode = unsafe { *protocol.mode };
+
+    Ok(GraphicsMode::from_info(mode.mode, unsafe { &*mode.info }))
+}
+
+pub fn set_graphics_mode(mode: &GraphicsMode) -> EfiResult<()> {
+    let protocol = get_graphics_output_protocol()?;
+    status_to_result(unsafe { (protocol.set_mode)(protocol, mode.number) })
+}
+
 #[derive(Debug)]
 pub struct MemoryMap {
     page_box: PageBox,
     entry_size: usize,
     entry_count: usize,
+    entry_version: UInt32,
     map_key: usize,
 }
 
@@ -398,8 +800,31 @@ impl MemoryMap {
         self.map_key
     }
 
-    pub fn get_memory_map() -> Self {
-        check_boot_services_is_avaiable().expect("use after exit_boot_services");
+    pub fn entry_count(&self) -> usize {
+        self.entry_count
+    }
+
+    pub fn iter(&self) -> MemoryMapIter<'_> {
+        MemoryMapIter {
+            memory_map: self,
+            index: 0,
+        }
+    }
+
+    // the firmware only guarantees 8 byte alignment of the entries
+    pub fn descriptor_mut(&mut self, index: usize) -> &mut EfiMemoryDescriptor {
+        assert!(index < self.entry_count);
+        let offset = index * self.entry_size;
+        assert!(offset + mem::size_of::<EfiMemoryDescriptor>() <= self.page_box.len());
+
+        let descriptor =
+            unsafe { self.page_box.as_mut_ptr().add(offset) } as *mut EfiMemoryDescriptor;
+        assert!(descriptor.is_aligned());
+        unsafe { &mut *descriptor }
+    }
+
+    pub fn get_memory_map() -> EfiResult<Self> {
+        check_boot_services_is_avaiable()?;
         let get_memory_map = unsafe { (*BOOT_SERVICES).get_memory_map };
 
         let mut count = 0;
@@ -423,7 +848,7 @@ impl MemoryMap {
 
             let mut page_box =
                 PageBox::new((memory_map_size + PageBox::PAGE_SIZE - 1) / PageBox::PAGE_SIZE);
-            if unsafe {
+            let result = status_to_result(unsafe {
                 (get_memory_map)(
                     &raw mut memory_map_size,
                     page_box.as_mut_ptr() as *mut _,
@@ -431,42 +856,115 @@ impl MemoryMap {
                     &raw mut descriptor_size,
                     &raw mut descriptor_version,
                 )
-            } == EFI_STATUS_SUCCESS
-            {
-                break Self {
-                    page_box: page_box,
-                    entry_size: descriptor_size,
-                    entry_count: memory_map_size / descriptor_size,
-                    map_key: map_key,
-                };
+            });
+            match result {
+                Ok(()) => {
+                    break Ok(Self {
+                        page_box: page_box,
+                        entry_size: descriptor_size,
+                        entry_count: memory_map_size / descriptor_size,
+                        entry_version: descriptor_version,
+                        map_key: map_key,
+                    });
+                }
+                // the map grew between the two calls, try again with the new size
+                Err(EfiError::BufferTooSmall) => (),
+                Err(error) => break Err(error),
             }
 
             count += 1;
 
             if MAX_COUNT == count {
-                panic!("failed to  get memory map");
+                break Err(EfiError::BufferTooSmall);
             }
         }
     }
 }
 
-pub unsafe fn exit_boot_services() -> MemoryMap {
+#[derive(Clone, Copy, Debug)]
+pub struct MemoryMapIter<'a> {
+    memory_map: &'a MemoryMap,
+    index: usize,
+}
+
+impl<'a> Iterator for MemoryMapIter<'a> {
+    type Item = EfiMemoryDescriptor;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.memory_map.entry_count {
+            let offset = self.index * self.memory_map.entry_size;
+            assert!(
+                offset + mem::size_of::<EfiMemoryDescriptor>() <= self.memory_map.page_box.len()
+            );
+            let descriptor = unsafe {
+                (self.memory_map.page_box.as_ptr().add(offset) as *const EfiMemoryDescriptor)
+                    .read_unaligned()
+            };
+            self.index += 1;
+
+            Some(descriptor)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn runtime_services_addr() -> Option<usize> {
+    runtime_services()
+        .ok()
+        .map(|runtime_services| (runtime_services as *const EfiRuntimeServices).addr())
+}
+
+// Switches the runtime services to the virtual addresses set in `memory_map`.
+// as-boot runs identity-mapped in firmware page tables, so it cannot call them anymore afterwards
+// and reset_system falls back to a chipset reset.
+pub unsafe fn set_virtual_address_map(memory_map: &MemoryMap) -> EfiResult<()> {
+    // only allowed after exit_boot_services
+    if check_boot_services_is_avaiable().is_ok() {
+        return Err(EfiError::Unsupported);
+    }
+    let runtime_services = runtime_services()?;
+
+    status_to_result(unsafe {
+        (runtime_services.set_virtual_address_map)(
+            memory_map.entry_count * memory_map.entry_size,
+            memory_map.entry_size,
+            memory_map.entry_version as UIntN,
+            memory_map.page_box.as_ptr() as *const EfiMemoryDescriptor,
+        )
+    })?;
+
+    unsafe {
+        SYSTEM_TABLE = ptr::null();
+    }
+    Ok(())
+}
+
+pub unsafe fn exit_boot_services() -> EfiResult<MemoryMap> {
     const MAX_COUNT: i32 = 10;
     let mut count = 0;
 
     let exit_boot_services = unsafe { (*BOOT_SERVICES).exit_boot_services };
     let image_handle = unsafe { IMAGE_HANDLE.expect("failed to get image handle") };
     loop {
-        let memory_map = MemoryMap::get_memory_map();
+        let memory_map = MemoryMap::get_memory_map()?;
 
-        if unsafe { (exit_boot_services)(image_handle, memory_map.map_key()) } == EFI_STATUS_SUCCESS
+        // a stale map key means the map changed, so fetch it again and retry
+        match status_to_result(unsafe { (exit_boot_services)(image_handle, memory_map.map_key()) })
         {
-            break memory_map;
+            Ok(()) => {
+                unsafe {
+                    BOOT_SERVICES = ptr::null();
+                }
+                break Ok(memory_map);
+            }
+            Err(EfiError::InvalidParameter) => (),
+            Err(error) => break Err(error),
         }
 
         count += 1;
         if count == MAX_COUNT {
-            panic!("failed to exit boot services");
+            break Err(EfiError::InvalidParameter);
         }
     }
 }
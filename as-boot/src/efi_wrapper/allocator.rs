@@ -0,0 +1,64 @@
+use super::*;
+use core::alloc::GlobalAlloc;
+use core::alloc::Layout;
+
+// Heap on top of allocate_pool. It only works while boot services are available;
+// after exit_boot_services allocating panics and freeing leaks, since the final
+// memory map has already been handed to the kernel.
+struct PoolAllocator;
+
+#[global_allocator]
+static ALLOCATOR: PoolAllocator = PoolAllocator;
+
+impl PoolAllocator {
+    // alignment guaranteed by allocate_pool
+    const POOL_ALIGN: usize = 8;
+}
+
+unsafe impl GlobalAlloc for PoolAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if check_boot_services_is_avaiable().is_err() {
+            panic!(
+                "heap allocation of {} bytes after exit_boot_services",
+                layout.size()
+            );
+        }
+
+        if layout.align() <= Self::POOL_ALIGN {
+            return alloc_pool(layout.size()).unwrap_or(ptr::null_mut());
+        }
+
+        // over-allocate and keep the pool pointer in the word right before the aligned block
+        let Ok(pool) = alloc_pool(layout.size() + layout.align()) else {
+            return ptr::null_mut();
+        };
+        let offset =
+            (pool.addr() + size_of::<usize>()).next_multiple_of(layout.align()) - pool.addr();
+        unsafe {
+            let aligned = pool.add(offset);
+            (aligned as *mut *mut u8).sub(1).write(pool);
+            aligned
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let pool = if layout.align() <= Self::POOL_ALIGN {
+            ptr
+        } else {
+            unsafe { (ptr as *mut *mut u8).sub(1).read() }
+        };
+        let _ = dealloc_pool(pool);
+    }
+}
+
+// pool memory is only 8 byte aligned, see PoolAllocator for larger alignments
+fn alloc_pool(size: usize) -> EfiResult<*mut u8> {
+    let boot_services = boot_services()?;
+    let mut buffer: *mut c_void = ptr::null_mut();
+
+    status_to_result(unsafe {
+        (boot_services.allocate_pool)(EfiMemoryType::EfiLoaderData, size, &raw mut buffer)
+    })?;
+
+    Ok(buffer as *mut u8)
+}
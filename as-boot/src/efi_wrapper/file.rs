@@ -0,0 +1,381 @@
+use super::static_str::StaticStr;
+use super::*;
+
+#[derive(Debug)]
+pub struct File {
+    protocol: *const EfiFileProtocol,
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        if check_boot_services_is_avaiable().is_ok() {
+            unsafe {
+                ((*self.protocol).close)(self.protocol);
+            }
+        }
+    }
+}
+
+impl File {
+    // the firmware may return less than asked for, so large reads are split into chunks
+    const READ_CHUNK: usize = 1024 * 1024;
+    const INFO_BUFF_SIZE: usize = 1024;
+    const CREATE_MODE: UInt64 = EfiFileProtocol::EFI_FILE_MODE_READ
+        | EfiFileProtocol::EFI_FILE_MODE_WRITE
+        | EfiFileProtocol::EFI_FILE_MODE_CREATE;
+
+    // `path` is looked up on the boot volume, or on volume N with an `fsN:` prefix
+    pub fn new(path: &str) -> EfiResult<Self> {
+        Self::open(path, EfiFileProtocol::EFI_FILE_MODE_READ)
+    }
+
+    // opens or creates `path` for writing and truncates it
+    pub fn create(path: &str) -> EfiResult<Self> {
+        let mut file = Self::open(path, Self::CREATE_MODE)?;
+        file.set_len(0)?;
+        Ok(file)
+    }
+
+    // opens or creates `path` for writing at its end
+    pub fn append(path: &str) -> EfiResult<Self> {
+        let mut file = Self::open(path, Self::CREATE_MODE)?;
+        file.set_position(EfiFileProtocol::POSITION_END)?;
+        Ok(file)
+    }
+
+    pub fn open(path: &str, mode: UInt64) -> EfiResult<Self> {
+        let (volume, path) = Volume::resolve(path)?;
+        volume.open(path, mode)
+    }
+
+    pub fn load(path: &str) -> EfiResult<(PageBox, usize)> {
+        let mut file = Self::new(path)?;
+        let file_size = file.size()?;
+        let mut page_box = PageBox::new_from_bytes(file_size);
+        let load_size = file.read_all(&mut page_box[..file_size])?;
+        if load_size != file_size {
+            return Err(EfiError::EndOfFile);
+        }
+
+        Ok((page_box, load_size))
+    }
+
+    pub fn size(&self) -> EfiResult<usize> {
+        Ok(self.info()?.size as usize)
+    }
+
+    pub fn info(&self) -> EfiResult<FileInfo> {
+        check_boot_services_is_avaiable()?;
+
+        let mut file_info_buff = [0u64; Self::INFO_BUFF_SIZE / 8];
+        let mut file_info_buff_size: UIntN = Self::INFO_BUFF_SIZE;
+        status_to_result(unsafe {
+            ((*self.protocol).get_info)(
+                self.protocol,
+                &EfiFileInfo::GUID,
+                &raw mut file_info_buff_size,
+                file_info_buff.as_mut_ptr() as *mut u8,
+            )
+        })?;
+
+        let bytes = unsafe {
+            slice::from_raw_parts(file_info_buff.as_ptr() as *const u8, file_info_buff_size)
+        };
+        FileInfo::parse(bytes).ok_or(EfiError::VolumeCorrupted)
+    }
+
+    pub fn set_len(&mut self, size: usize) -> EfiResult<()> {
+        check_boot_services_is_avaiable()?;
+
+        let mut file_info_buff = [0u64; Self::INFO_BUFF_SIZE / 8];
+        let mut file_info_buff_size: UIntN = Self::INFO_BUFF_SIZE;
+        let file_info_ptr = file_info_buff.as_mut_ptr() as *mut u8;
+        unsafe {
+            let protocol = &*self.protocol;
+            status_to_result((protocol.get_info)(
+                self.protocol,
+                &EfiFileInfo::GUID,
+                &raw mut file_info_buff_size,
+                file_info_ptr,
+            ))?;
+            (*(file_info_ptr as *mut EfiFileInfo)).file_size = size as UInt64;
+            status_to_result((protocol.set_info)(
+                self.protocol,
+                &EfiFileInfo::GUID,
+                file_info_buff_size,
+                file_info_ptr,
+            ))
+        }
+    }
+
+    // a single firmware read, may return less than `buf.len()`
+    pub fn read(&mut self, buf: &mut [u8]) -> EfiResult<usize> {
+        check_boot_services_is_avaiable()?;
+
+        let mut buffer_size: UIntN = buf.len();
+        status_to_result(unsafe {
+            ((*self.protocol).read)(self.protocol, &raw mut buffer_size, buf.as_mut_ptr())
+        })?;
+
+        Ok(buffer_size)
+    }
+
+    // reads until `buf` is full or the end of the file
+    pub fn read_all(&mut self, buf: &mut [u8]) -> EfiResult<usize> {
+        let mut offset = 0;
+        while offset < buf.len() {
+            let end = buf.len().min(offset + Self::READ_CHUNK);
+            let read_size = self.read(&mut buf[offset..end])?;
+            if read_size == 0 {
+                break;
+            }
+            offset += read_size;
+        }
+
+        Ok(offset)
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> EfiResult<usize> {
+        check_boot_services_is_avaiable()?;
+
+        let mut buffer_size: UIntN = data.len();
+        status_to_result(unsafe {
+            ((*self.protocol).write)(self.protocol, &raw mut buffer_size, data.as_ptr())
+        })?;
+
+        Ok(buffer_size)
+    }
+
+    pub fn write_all(&mut self, mut data: &[u8]) -> EfiResult<()> {
+        while !data.is_empty() {
+            let write_size = self.write(data)?;
+            if write_size == 0 {
+                return Err(EfiError::VolumeFull);
+            }
+            data = &data[write_size..];
+        }
+
+        Ok(())
+    }
+
+    pub fn set_position(&mut self, position: u64) -> EfiResult<()> {
+        check_boot_services_is_avaiable()?;
+
+        status_to_result(unsafe { ((*self.protocol).set_position)(self.protocol, position) })
+    }
+
+    pub fn flush(&mut self) -> EfiResult<()> {
+        check_boot_services_is_avaiable()?;
+
+        status_to_result(unsafe { ((*self.protocol).flush)(self.protocol) })
+    }
+
+    // entries of a directory opened with `new`
+    pub fn read_dir(&mut self) -> ReadDir<'_> {
+        ReadDir {
+            dir: self,
+            done: false,
+        }
+    }
+}
+
+impl fmt::Write for File {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct FileInfo {
+    pub name: StaticStr<{ FileInfo::NAME_MAX }>,
+    pub size: u64,
+    pub attribute: u64,
+    pub modification_time: EfiTime,
+}
+
+impl FileInfo {
+    pub const NAME_MAX: usize = 256;
+
+    pub fn is_directory(&self) -> bool {
+        self.attribute & EfiFileProtocol::EFI_FILE_DIRECTORY != 0
+    }
+
+    // `bytes` is an EFI_FILE_INFO with its trailing name
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < mem::size_of::<EfiFileInfo>() {
+            return None;
+        }
+        let file_info = unsafe { (bytes.as_ptr() as *const EfiFileInfo).read_unaligned() };
+
+        let name_bytes = &bytes[mem::size_of::<EfiFileInfo>()..];
+        let name_utf16 = name_bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0);
+        let mut name = StaticStr::new();
+        for c in char::decode_utf16(name_utf16) {
+            if name
+                .write_char(c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .is_err()
+            {
+                break;
+            }
+        }
+
+        Some(Self {
+            name: name,
+            size: file_info.file_size,
+            attribute: file_info.attribute,
+            modification_time: file_info.modification_time,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ReadDir<'a> {
+    dir: &'a mut File,
+    // set after the end or the first error, the firmware would likely fail the same way again
+    done: bool,
+}
+
+impl<'a> Iterator for ReadDir<'a> {
+    type Item = EfiResult<FileInfo>;
+
+    // reading a directory returns one EFI_FILE_INFO per call and 0 bytes at the end
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut buff = [0u64; File::INFO_BUFF_SIZE / 8];
+        let buff_bytes = unsafe {
+            slice::from_raw_parts_mut(buff.as_mut_ptr() as *mut u8, File::INFO_BUFF_SIZE)
+        };
+
+        match self.dir.read(buff_bytes) {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(size) => Some(FileInfo::parse(&buff_bytes[..size]).ok_or(EfiError::VolumeCorrupted)),
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+// a handle with EfiSimpleFileSystemProtocol
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Volume {
+    handle: EfiHandle,
+}
+
+impl Volume {
+    pub fn handle(&self) -> EfiHandle {
+        self.handle
+    }
+
+    pub fn open(&self, path: &str, mode: UInt64) -> EfiResult<File> {
+        let root = self.open_root()?;
+        let path_utf16 = utf16::as_utf16::<1024>(path);
+
+        let mut protocol = ptr::null();
+        status_to_result(unsafe {
+            ((*root.protocol).open)(
+                root.protocol,
+                &raw mut protocol,
+                path_utf16.as_ptr(),
+                mode,
+                0,
+            )
+        })?;
+
+        Ok(File { protocol: protocol })
+    }
+
+    fn open_root(&self) -> EfiResult<File> {
+        // volumes are only built from handles the firmware returned
+        let file_system = unsafe {
+            boot_services()?.handle_protocol::<EfiSimpleFileSystemProtocol>(self.handle)?
+        };
+
+        let mut protocol = ptr::null();
+        status_to_result((file_system.open_volume)(file_system, &raw mut protocol))?;
+
+        Ok(File { protocol: protocol })
+    }
+
+    // "fs1:\kernel.elf" -> (Some(1), "\kernel.elf")
+    // the volume `path` refers to and the path on that volume
+    pub fn resolve(path: &str) -> EfiResult<(Volume, &str)> {
+        match Self::split_path(path) {
+            (Some(index), path) => Ok((volumes()?.get(index).ok_or(EfiError::NotFound)?, path)),
+            (None, path) => Ok((boot_volume()?, path)),
+        }
+    }
+
+    fn split_path(path: &str) -> (Option<usize>, &str) {
+        let Some((prefix, rest)) = path.split_once(':') else {
+            return (None, path);
+        };
+        match prefix
+            .strip_prefix("fs")
+            .and_then(|index| index.parse().ok())
+        {
+            Some(index) => (Some(index), rest),
+            None => (None, path),
+        }
+    }
+}
+
+// the volume as-boot itself was loaded from
+pub fn boot_volume() -> EfiResult<Volume> {
+    let image_handle = unsafe { IMAGE_HANDLE }.ok_or(EfiError::NotReady)?;
+    let loaded_image =
+        unsafe { boot_services()?.handle_protocol::<EfiLoadedImageProtocol>(image_handle)? };
+
+    Ok(Volume {
+        handle: loaded_image.device_handle,
+    })
+}
+
+#[derive(Clone, Debug)]
+pub struct Volumes {
+    handles: Vec<EfiHandle>,
+}
+
+impl Volumes {
+    pub fn get(&self, index: usize) -> Option<Volume> {
+        self.handles
+            .get(index)
+            .map(|handle| Volume { handle: *handle })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Volume> + '_ {
+        self.handles.iter().map(|handle| Volume { handle: *handle })
+    }
+}
+
+// all file systems in locate_handle order, which is what `fsN:` refers to
+pub fn volumes() -> EfiResult<Volumes> {
+    let boot_services = boot_services()?;
+    loop {
+        let count = boot_services.handle_count::<EfiSimpleFileSystemProtocol>()?;
+        let mut handles = vec![ptr::null_mut(); count];
+        // a file system may show up between the two calls, then ask again
+        match boot_services.locate_handles::<EfiSimpleFileSystemProtocol>(&mut handles) {
+            Ok(len) => {
+                handles.truncate(len);
+                return Ok(Volumes { handles: handles });
+            }
+            Err(EfiError::NotFound) => {
+                return Ok(Volumes {
+                    handles: Vec::new(),
+                });
+            }
+            Err(EfiError::BufferTooSmall) => continue,
+            Err(error) => return Err(error),
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use super::*;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter;
+
+#[derive(Clone, Debug)]
+pub struct ImageExit {
+    pub status: EfiStatus,
+    // exit data of the application, usually an error message
+    pub message: String,
+}
+
+// Runs `buff`, the EFI application loaded from `path`, until it calls Exit.
+// `load_options` is handed over as its LoadOptions (UCS-2, NUL terminated),
+// which is where the UEFI shell and most loaders read their command line from.
+pub fn start_image(path: &str, buff: &[u8], load_options: &str) -> EfiResult<ImageExit> {
+    let boot_services = boot_services()?;
+    let parent = unsafe { IMAGE_HANDLE }.ok_or(EfiError::NotReady)?;
+    let (volume, _) = Volume::resolve(path)?;
+
+    let mut image: EfiHandle = ptr::null_mut();
+    let status = unsafe {
+        (boot_services.load_image)(
+            false,
+            parent,
+            ptr::null(),
+            buff.as_ptr() as *const c_void,
+            buff.len(),
+            &raw mut image,
+        )
+    };
+    if let Err(error) = status_to_result(status) {
+        // a handle is still created when the image fails verification
+        if !image.is_null() {
+            unsafe { (boot_services.unload_image)(image) };
+        }
+        return Err(error);
+    }
+
+    let load_options: Vec<Char16> = load_options.encode_utf16().chain(iter::once(0)).collect();
+    let loaded_image =
+        unsafe { boot_services.handle_protocol_mut::<EfiLoadedImageProtocol>(image)? };
+    // the image has not started yet, so nothing else is touching its loaded image protocol
+    unsafe {
+        (*loaded_image).load_options = load_options.as_ptr() as *const c_void;
+        (*loaded_image).load_options_size = (load_options.len() * size_of::<Char16>()) as UInt32;
+        // without a device path the firmware cannot tell where the image came from, but
+        // applications look for their own files on this volume
+        if (*loaded_image).device_handle.is_null() {
+            (*loaded_image).device_handle = volume.handle();
+        }
+    }
+
+    let mut exit_data_size: UIntN = 0;
+    let mut exit_data: *mut Char16 = ptr::null_mut();
+    let status =
+        unsafe { (boot_services.start_image)(image, &raw mut exit_data_size, &raw mut exit_data) };
+
+    let mut message = String::new();
+    if !exit_data.is_null() {
+        let exit_data =
+            unsafe { slice::from_raw_parts(exit_data, exit_data_size / size_of::<Char16>()) };
+        message.extend(
+            char::decode_utf16(exit_data.iter().copied().take_while(|c| *c != 0))
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)),
+        );
+        let _ = dealloc_pool(exit_data.as_ptr() as *mut u8);
+    }
+
+    Ok(ImageExit {
+        status: status,
+        message: message,
+    })
+}
@@ -0,0 +1,127 @@
+use super::static_str::StaticStr;
+use super::*;
+use bootgfx::Color;
+use core::panic::PanicInfo;
+
+static mut PANICKING: bool = false;
+
+const RESET_TIMEOUT_SECS: usize = 10;
+const BANNER_ROWS: usize = 3;
+const BANNER_BACKGROUND: Color = Color::new(0xC0, 0x1C, 0x28);
+const BANNER_FOREGROUND: Color = Color::new(0xFF, 0xFF, 0xFF);
+
+// Works in every phase: the message goes through a fixed buffer to COM1 and the
+// framebuffer, neither of which needs boot services.
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    // a panic while reporting a panic (e.g. inside the terminal) only goes to serial
+    let nested = unsafe { mem::replace(&mut *(&raw mut PANICKING), true) };
+
+    let mut location = StaticStr::<256>::new();
+    let _ = match info.location() {
+        Some(l) => write!(&mut location, "{}:{}:{}", l.file(), l.line(), l.column()),
+        None => write!(&mut location, "unknown location"),
+    };
+    let mut message = StaticStr::<1024>::new();
+    let truncated = write!(&mut message, "{}", info.message()).is_err();
+
+    let output = |s: &str| {
+        if nested {
+            write_serial(s);
+        } else {
+            write_stdout(s);
+        }
+    };
+
+    if !nested {
+        clear_terminal();
+        for _ in 0..BANNER_ROWS {
+            write_terminal("\n\r");
+        }
+    }
+    write_serial("\n\r");
+    output("PANIC: at ");
+    output(&location);
+    output("\n\r");
+    output(&message);
+    if truncated {
+        output(" ...");
+    }
+    output("\n\r\n\r");
+    if !nested {
+        draw_banner();
+        // the crash report ends up in the log file while the ESP is still reachable
+        let _ = write_log_file();
+    }
+
+    // the keyboard is only readable while boot services are alive
+    let can_wait_for_key = !nested && check_boot_services_is_avaiable().is_ok();
+    for remaining in (1..=RESET_TIMEOUT_SECS).rev() {
+        let mut countdown = StaticStr::<64>::new();
+        let _ = write!(&mut countdown, "\rresetting in {:2} seconds", remaining);
+        if can_wait_for_key {
+            let _ = write!(&mut countdown, ", press any key to reset now");
+        }
+        output(&countdown);
+
+        if can_wait_for_key {
+            if wait_for_key(Some(1000)).is_some() {
+                break;
+            }
+        } else {
+            delay_ms(1000);
+        }
+    }
+    output("\n\r");
+
+    reset_system()
+}
+
+fn draw_banner() {
+    let Some(terminal) = (unsafe { &mut *(&raw mut TERMINAL) }) else {
+        return;
+    };
+
+    let width = terminal.width();
+    terminal.draw_rect(0, 0, width, BANNER_ROWS * 16, BANNER_BACKGROUND);
+    terminal.draw_str(
+        "as-boot panicked",
+        16,
+        16,
+        BANNER_FOREGROUND,
+        BANNER_BACKGROUND,
+    );
+}
+
+fn reset_system() -> ! {
+    if let Ok(runtime_services) = runtime_services() {
+        unsafe {
+            (runtime_services.reset_system)(
+                EfiResetType::EfiResetCold,
+                EFI_STATUS_SUCCESS,
+                0,
+                ptr::null(),
+            )
+        }
+    }
+
+    // After set_virtual_address_map the runtime services only answer at their new addresses,
+    // which are not mapped yet, so reset through the chipset instead.
+    const RESET_CONTROL: u16 = 0xcf9;
+    const RESET_CONTROL_FULL_RESET: u8 = 0x0e;
+    const KEYBOARD_CONTROLLER: u16 = 0x64;
+    const KEYBOARD_CONTROLLER_PULSE_RESET: u8 = 0xfe;
+    unsafe {
+        core::arch::asm!("out dx, al", in("dx") RESET_CONTROL, in("al") RESET_CONTROL_FULL_RESET);
+        core::arch::asm!(
+            "out dx, al",
+            in("dx") KEYBOARD_CONTROLLER,
+            in("al") KEYBOARD_CONTROLLER_PULSE_RESET
+        );
+    }
+    loop {
+        unsafe {
+            core::arch::asm!("hlt");
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use core::fmt;
+use efi::EfiError;
+use efi::EfiResult;
+
+pub type BootResult<T> = Result<T, BootError>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BootError {
+    // what as-boot was doing, and the status the firmware returned
+    Efi(&'static str, EfiError),
+    Message(&'static str),
+}
+
+impl From<&'static str> for BootError {
+    fn from(msg: &'static str) -> Self {
+        Self::Message(msg)
+    }
+}
+
+impl fmt::Display for BootError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Efi(context, error) => write!(f, "{}: {}", context, error),
+            Self::Message(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+pub trait Context<T> {
+    fn context(self, context: &'static str) -> BootResult<T>;
+}
+
+impl<T> Context<T> for EfiResult<T> {
+    fn context(self, context: &'static str) -> BootResult<T> {
+        self.map_err(|error| BootError::Efi(context, error))
+    }
+}
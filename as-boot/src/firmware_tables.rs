@@ -0,0 +1,156 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::LogLevel;
+use core::slice;
+use efi::EfiConfigurationTable;
+use efi::EfiGuid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rsdp {
+    pub addr: usize,
+    pub revision: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Smbios {
+    pub addr: usize,
+    pub major_version: u8,
+    pub minor_version: u8,
+}
+
+// ACPI 2.0+ is preferred, the 1.0 RSDP is only used when no valid 2.0 table exists
+pub fn find_rsdp() -> Option<Rsdp> {
+    let guids = [
+        EfiConfigurationTable::ACPI_20_TABLE_GUID,
+        EfiConfigurationTable::ACPI_TABLE_GUID,
+    ];
+
+    guids.iter().find_map(|guid| {
+        let addr = find_table(guid)?;
+        match validate_rsdp(addr) {
+            Ok(revision) => Some(Rsdp {
+                addr: addr,
+                revision: revision,
+            }),
+            Err(msg) => {
+                log!(LogLevel::Warn, "ACPI: RSDP at {:#x}: {}", addr, msg);
+                None
+            }
+        }
+    })
+}
+
+// SMBIOS 3 is preferred, the 32bit entry point is only used when no valid 64bit one exists
+pub fn find_smbios() -> Option<Smbios> {
+    let smbios3 = find_table(&EfiConfigurationTable::SMBIOS3_TABLE_GUID).and_then(|addr| {
+        match validate_smbios3(addr) {
+            Ok((major_version, minor_version)) => Some(Smbios {
+                addr: addr,
+                major_version: major_version,
+                minor_version: minor_version,
+            }),
+            Err(msg) => {
+                log!(
+                    LogLevel::Warn,
+                    "SMBIOS: entry point at {:#x}: {}",
+                    addr,
+                    msg
+                );
+                None
+            }
+        }
+    });
+
+    smbios3.or_else(|| {
+        let addr = find_table(&EfiConfigurationTable::SMBIOS_TABLE_GUID)?;
+        match validate_smbios(addr) {
+            Ok((major_version, minor_version)) => Some(Smbios {
+                addr: addr,
+                major_version: major_version,
+                minor_version: minor_version,
+            }),
+            Err(msg) => {
+                log!(
+                    LogLevel::Warn,
+                    "SMBIOS: entry point at {:#x}: {}",
+                    addr,
+                    msg
+                );
+                None
+            }
+        }
+    })
+}
+
+fn find_table(guid: &EfiGuid) -> Option<usize> {
+    efi_wrapper::configuration_table(guid)
+        .filter(|table| !table.is_null())
+        .map(|table| table.addr())
+}
+
+fn validate_rsdp(addr: usize) -> Result<u8, &'static str> {
+    const RSDP_V1_LENGTH: usize = 20;
+
+    let rsdp = unsafe { bytes(addr, RSDP_V1_LENGTH) };
+    if &rsdp[0..8] != b"RSD PTR " {
+        return Err("invalid signature");
+    }
+    if checksum(rsdp) != 0 {
+        return Err("invalid checksum");
+    }
+
+    let revision = rsdp[15];
+    if 2 <= revision {
+        let length = unsafe { (addr as *const u8).add(20).cast::<u32>().read_unaligned() };
+        if (length as usize) < RSDP_V1_LENGTH {
+            return Err("invalid length");
+        }
+        if checksum(unsafe { bytes(addr, length as usize) }) != 0 {
+            return Err("invalid extended checksum");
+        }
+    }
+
+    Ok(revision)
+}
+
+fn validate_smbios(addr: usize) -> Result<(u8, u8), &'static str> {
+    let header = unsafe { bytes(addr, 0x1f) };
+    if &header[0..4] != b"_SM_" {
+        return Err("invalid anchor");
+    }
+    let length = header[5] as usize;
+    if length < 0x1f {
+        return Err("invalid length");
+    }
+    if checksum(unsafe { bytes(addr, length) }) != 0 {
+        return Err("invalid checksum");
+    }
+    if &header[0x10..0x15] != b"_DMI_" || checksum(&header[0x10..0x1f]) != 0 {
+        return Err("invalid intermediate checksum");
+    }
+
+    Ok((header[6], header[7]))
+}
+
+fn validate_smbios3(addr: usize) -> Result<(u8, u8), &'static str> {
+    let header = unsafe { bytes(addr, 0x18) };
+    if &header[0..5] != b"_SM3_" {
+        return Err("invalid anchor");
+    }
+    let length = header[6] as usize;
+    if length < 0x18 {
+        return Err("invalid length");
+    }
+    if checksum(unsafe { bytes(addr, length) }) != 0 {
+        return Err("invalid checksum");
+    }
+
+    Ok((header[7], header[8]))
+}
+
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+unsafe fn bytes<'a>(addr: usize, len: usize) -> &'a [u8] {
+    unsafe { slice::from_raw_parts(addr as *const u8, len) }
+}
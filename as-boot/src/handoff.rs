@@ -0,0 +1,107 @@
+use crate::efi_wrapper::PageBox;
+use crate::paging::PageTable;
+use bootgfx::FrameBuffer;
+use bootgfx::FrameBufferMode;
+use bootinfo::BootInfo;
+use bootinfo::FrameBufferInfo;
+use bootinfo::PixelFormat;
+use core::arch::global_asm;
+
+#[derive(Debug)]
+pub struct KernelStack {
+    top: usize,
+}
+
+impl KernelStack {
+    pub const PAGES: usize = 16;
+
+    pub fn new() -> Self {
+        let stack: &'static mut [u8] = PageBox::new(Self::PAGES).leak();
+        let top = stack.as_ptr().addr() + stack.len();
+
+        Self { top: top }
+    }
+
+    pub fn top(&self) -> usize {
+        self.top
+    }
+}
+
+pub fn alloc_boot_info() -> &'static mut BootInfo {
+    let pages = (size_of::<BootInfo>() + PageBox::PAGE_SIZE - 1) / PageBox::PAGE_SIZE;
+    let page: &'static mut [u8] = PageBox::new(pages).leak();
+    let boot_info = page.as_mut_ptr() as *mut BootInfo;
+
+    unsafe {
+        boot_info.write(BootInfo::new());
+        &mut *boot_info
+    }
+}
+
+pub fn leak_bytes(bytes: &[u8]) -> &'static [u8] {
+    if bytes.is_empty() {
+        return &[];
+    }
+
+    let buff: &'static mut [u8] = PageBox::new_from_bytes(bytes.len()).leak();
+    let buff = &mut buff[..bytes.len()];
+    buff.copy_from_slice(bytes);
+    buff
+}
+
+pub fn frame_buffer_info(frame_buffer: &FrameBuffer) -> FrameBufferInfo {
+    let format = match frame_buffer.mode() {
+        FrameBufferMode::RGB => PixelFormat::Rgb,
+        FrameBufferMode::BGR => PixelFormat::Bgr,
+        FrameBufferMode::Unknown => PixelFormat::Unknown,
+    };
+
+    FrameBufferInfo {
+        base: frame_buffer.base_ptr().addr() as u64,
+        width: frame_buffer.width() as u64,
+        height: frame_buffer.height() as u64,
+        stride: frame_buffer.scanline_pixels() as u64,
+        format: format,
+    }
+}
+
+// The kernel is entered with the System V AMD64 calling convention:
+// rdi holds the address of the BootInfo, rsp points to a 16-byte aligned stack with a null return address,
+// and interrupts are disabled.
+global_asm!(
+    ".global as_boot_trampoline",
+    ".global as_boot_trampoline_end",
+    "as_boot_trampoline:",
+    "cli",
+    "mov cr3, rsi",
+    "mov rsp, rdx",
+    "xor ebp, ebp",
+    "push rbp",
+    "jmp rcx",
+    "as_boot_trampoline_end:",
+);
+
+unsafe extern "sysv64" {
+    fn as_boot_trampoline(boot_info: usize, pml4: usize, stack_top: usize, entry_point: usize)
+    -> !;
+    fn as_boot_trampoline_end();
+}
+
+pub fn trampoline_range() -> (usize, usize) {
+    (
+        as_boot_trampoline as *const () as usize,
+        as_boot_trampoline_end as *const () as usize,
+    )
+}
+
+pub unsafe fn jump_to_kernel(
+    entry_point: usize,
+    page_table: &PageTable,
+    stack: &KernelStack,
+    boot_info: &BootInfo,
+) -> ! {
+    let boot_info_addr = (boot_info as *const BootInfo).addr();
+
+    PageTable::enable_protection();
+    unsafe { as_boot_trampoline(boot_info_addr, page_table.cr3(), stack.top(), entry_point) }
+}
@@ -0,0 +1,39 @@
+use crate::efi_wrapper::LogLevel;
+use crate::random;
+
+// A position-independent kernel is moved up from its link address by a random
+// multiple of SLIDE_ALIGN below SLIDE_RANGE. Kernels linked at 0xffffffff80000000
+// stay inside the top 2GiB, which the kernel code model relies on.
+const SLIDE_ALIGN: usize = 2 * 1024 * 1024;
+const SLIDE_RANGE: usize = 1024 * 1024 * 1024;
+// as-boot identity maps the lower half, a slid kernel has to stay above it
+const HIGHER_HALF_START: usize = 0xffff_8000_0000_0000;
+
+// 0 if there is no source of randomness
+pub fn choose_slide(link_addr: usize, size: usize) -> Result<usize, &'static str> {
+    if link_addr < HIGHER_HALF_START {
+        return Err("position-independent kernel is not linked in the higher half");
+    }
+    let end = link_addr
+        .checked_add(size)
+        .ok_or("kernel image overflows the address space")?;
+    let room = (SLIDE_RANGE - SLIDE_ALIGN).min(usize::MAX - end);
+    let slots = room / SLIDE_ALIGN + 1;
+
+    let Some((value, source)) = random::random_u64() else {
+        log!(
+            LogLevel::Warn,
+            "KASLR: no random source available, loading at the link address"
+        );
+        return Ok(0);
+    };
+    let slide = (value % slots as u64) as usize * SLIDE_ALIGN;
+    log!(
+        LogLevel::Info,
+        "KASLR: slide {:#x} ({} slots, random from {:?})",
+        slide,
+        slots,
+        source
+    );
+    Ok(slide)
+}
@@ -0,0 +1,823 @@
+use crate::efi_wrapper::LogLevel;
+use crate::efi_wrapper::PageBox;
+use crate::handoff::KernelStack;
+use crate::paging::PageTable;
+use crate::smp;
+use crate::smp::ApStartup;
+use crate::smp::Gdtr;
+use crate::smp::Lapic;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use bootinfo::BootInfo;
+use bootinfo::CpuInfo;
+use bootinfo::MemoryRegion;
+use bootinfo::MemoryRegionKind;
+use bootinfo::PixelFormat;
+use core::arch::global_asm;
+use core::mem::offset_of;
+use core::ptr;
+use core::slice;
+
+// Limine boot protocol, see PROTOCOL.md in the Limine repository.
+// A Limine kernel declares requests as 8-byte aligned structs anywhere in its image, each
+// starting with COMMON_MAGIC and a request id. as-boot answers the ones it knows by pointing
+// `response` at a structure in bootloader-reclaimable memory, addressed through the HHDM
+// (higher half direct map, all of physical memory mapped at HHDM_OFFSET).
+
+pub const HHDM_OFFSET: usize = 0xffff_8000_0000_0000;
+
+const COMMON_MAGIC: [u64; 2] = [0xc7b1dd30df4c8b88, 0x0a82e883a194f07b];
+const BASE_REVISION_MAGIC: [u64; 2] = [0xf9562b2d5c95a6c8, 0x6a7b384944536bdc];
+// from base revision 3 on, the RSDP address is physical instead of an HHDM address
+const MAX_BASE_REVISION: u64 = 3;
+
+const MEMMAP_REQUEST: [u64; 2] = [0x67cf3d9d378a806f, 0xe304acdfc50c3c62];
+const FRAMEBUFFER_REQUEST: [u64; 2] = [0x9d5827dcd881dd75, 0xa3148604f6fab11b];
+const HHDM_REQUEST: [u64; 2] = [0x48dcf1cb8ad2b852, 0x63984e959a98244b];
+const RSDP_REQUEST: [u64; 2] = [0xc5e77b6b397e7b43, 0x27637845accdcf3c];
+const KERNEL_ADDRESS_REQUEST: [u64; 2] = [0x71ba76863cc55f63, 0xb2644a48c516a487];
+const MODULE_REQUEST: [u64; 2] = [0x3e7e279702be32af, 0xca1c4f3bd1280cee];
+const MP_REQUEST: [u64; 2] = [0x95a67b819a1b857e, 0xa0b61b723b6a73e0];
+
+const MEMMAP_USABLE: u64 = 0;
+const MEMMAP_RESERVED: u64 = 1;
+const MEMMAP_ACPI_RECLAIMABLE: u64 = 2;
+const MEMMAP_ACPI_NVS: u64 = 3;
+const MEMMAP_BOOTLOADER_RECLAIMABLE: u64 = 5;
+const MEMMAP_KERNEL_AND_MODULES: u64 = 6;
+const MEMMAP_FRAMEBUFFER: u64 = 7;
+
+const FRAMEBUFFER_RGB: u8 = 1;
+const MP_X2APIC: u32 = 1 << 0;
+const AP_STACK_PAGES: usize = 16;
+
+#[repr(C)]
+#[derive(Debug)]
+struct Request {
+    id: [u64; 4],
+    revision: u64,
+    response: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MemmapResponse {
+    revision: u64,
+    entry_count: u64,
+    entries: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+struct MemmapEntry {
+    base: u64,
+    length: u64,
+    kind: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct FramebufferResponse {
+    revision: u64,
+    framebuffer_count: u64,
+    framebuffers: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct Framebuffer {
+    address: u64,
+    width: u64,
+    height: u64,
+    pitch: u64,
+    bpp: u16,
+    memory_model: u8,
+    red_mask_size: u8,
+    red_mask_shift: u8,
+    green_mask_size: u8,
+    green_mask_shift: u8,
+    blue_mask_size: u8,
+    blue_mask_shift: u8,
+    unused: [u8; 7],
+    edid_size: u64,
+    edid: u64,
+    mode_count: u64,
+    modes: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct HhdmResponse {
+    revision: u64,
+    offset: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct RsdpResponse {
+    revision: u64,
+    address: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct KernelAddressResponse {
+    revision: u64,
+    physical_base: u64,
+    virtual_base: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct ModuleResponse {
+    revision: u64,
+    module_count: u64,
+    modules: u64,
+}
+
+// limine_file, the media fields are left zero
+#[repr(C)]
+#[derive(Debug)]
+struct ModuleFile {
+    revision: u64,
+    address: u64,
+    size: u64,
+    path: u64,
+    cmdline: u64,
+    media_type: u32,
+    unused: u32,
+    tftp_ip: u32,
+    tftp_port: u32,
+    partition_index: u32,
+    mbr_disk_id: u32,
+    gpt_disk_uuid: [u8; 16],
+    gpt_part_uuid: [u8; 16],
+    part_uuid: [u8; 16],
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MpResponse {
+    revision: u64,
+    flags: u32,
+    bsp_lapic_id: u32,
+    cpu_count: u64,
+    cpus: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MpInfo {
+    processor_id: u32,
+    lapic_id: u32,
+    reserved: u64,
+    // written by the kernel to release the AP, see limine_ap_park
+    goto_address: u64,
+    extra_argument: u64,
+}
+
+// requests found in the kernel image, pointing into the loaded kernel
+#[derive(Debug, Default)]
+pub struct Requests {
+    base_revision: Option<*mut [u64; 3]>,
+    memmap: Option<*mut Request>,
+    framebuffer: Option<*mut Request>,
+    hhdm: Option<*mut Request>,
+    rsdp: Option<*mut Request>,
+    kernel_address: Option<*mut Request>,
+    module: Option<*mut Request>,
+    mp: Option<*mut Request>,
+    count: usize,
+}
+
+impl Requests {
+    // None if the image has neither a request nor a base revision tag, i.e. it is not a Limine kernel
+    pub fn scan(image: &mut [u8]) -> Option<Self> {
+        let words = unsafe {
+            slice::from_raw_parts_mut(
+                image.as_mut_ptr() as *mut u64,
+                image.len() / size_of::<u64>(),
+            )
+        };
+        let mut requests = Self::default();
+        let mut found = false;
+
+        for index in 0..words.len().saturating_sub(1) {
+            let magic = [words[index], words[index + 1]];
+            if magic == BASE_REVISION_MAGIC && index + 3 <= words.len() {
+                requests.base_revision = Some(words[index..].as_mut_ptr() as *mut [u64; 3]);
+                found = true;
+            } else if magic == COMMON_MAGIC && index + size_of::<Request>() / 8 <= words.len() {
+                found = true;
+                let id = [words[index + 2], words[index + 3]];
+                let request = Some(words[index..].as_mut_ptr() as *mut Request);
+                match id {
+                    MEMMAP_REQUEST => requests.memmap = request,
+                    FRAMEBUFFER_REQUEST => requests.framebuffer = request,
+                    HHDM_REQUEST => requests.hhdm = request,
+                    RSDP_REQUEST => requests.rsdp = request,
+                    KERNEL_ADDRESS_REQUEST => requests.kernel_address = request,
+                    MODULE_REQUEST => requests.module = request,
+                    MP_REQUEST => requests.mp = request,
+                    _ => {
+                        log!(
+                            LogLevel::Debug,
+                            "LIMINE: ignoring request {:#x} {:#x}",
+                            id[0],
+                            id[1]
+                        );
+                        continue;
+                    }
+                }
+                requests.count += 1;
+            }
+        }
+
+        if found { Some(requests) } else { None }
+    }
+}
+
+#[derive(Debug)]
+pub struct LimineBoot {
+    requests: Requests,
+    base_revision: u64,
+    gdtr: &'static Gdtr,
+    memmap: Option<Memmap>,
+    mp: Option<Mp>,
+}
+
+impl LimineBoot {
+    // Answers every request that does not depend on the final memory map. `boot_info` has to be
+    // filled already, except for its memory map. Must be called while boot services are available.
+    pub fn new(requests: Requests, boot_info: &BootInfo) -> Self {
+        let base_revision = match requests.base_revision {
+            Some(tag) => {
+                let tag = unsafe { &mut *tag };
+                if tag[2] <= MAX_BASE_REVISION {
+                    let revision = tag[2];
+                    tag[1] = revision;
+                    tag[2] = 0;
+                    revision
+                } else {
+                    log!(
+                        LogLevel::Warn,
+                        "LIMINE: base revision {} is not supported",
+                        tag[2]
+                    );
+                    0
+                }
+            }
+            None => 0,
+        };
+
+        let gdt: &'static [u64] = Box::leak(Box::new(smp::GDT));
+        let gdtr: &'static Gdtr = Box::leak(Box::new(Gdtr {
+            limit: (size_of_val(gdt) - 1) as u16,
+            base: hhdm(gdt.as_ptr()),
+        }));
+
+        let mut limine = Self {
+            requests: requests,
+            base_revision: base_revision,
+            gdtr: gdtr,
+            memmap: None,
+            mp: None,
+        };
+
+        if let Some(request) = limine.requests.hhdm {
+            respond(
+                request,
+                HhdmResponse {
+                    revision: 0,
+                    offset: HHDM_OFFSET as u64,
+                },
+            );
+        }
+        if let Some(request) = limine.requests.kernel_address {
+            respond(
+                request,
+                KernelAddressResponse {
+                    revision: 0,
+                    physical_base: boot_info.kernel.physical_base,
+                    virtual_base: boot_info.kernel.virtual_base,
+                },
+            );
+        }
+        if let Some(request) = limine.requests.rsdp {
+            if boot_info.rsdp != 0 {
+                let address = if 3 <= base_revision {
+                    boot_info.rsdp
+                } else {
+                    hhdm(boot_info.rsdp as usize as *const u8)
+                };
+                respond(
+                    request,
+                    RsdpResponse {
+                        revision: 0,
+                        address: address,
+                    },
+                );
+            }
+        }
+        if let Some(request) = limine.requests.framebuffer {
+            limine.respond_framebuffer(request, boot_info);
+        }
+        if let Some(request) = limine.requests.module {
+            limine.respond_modules(request, boot_info);
+        }
+        if limine.requests.mp.is_some() {
+            limine.mp = Mp::new(boot_info);
+        }
+
+        limine
+    }
+
+    // Last allocation before exit_boot_services, so that `memory_map_entries` (the capacity of
+    // MemoryRegions) still bounds the final memory map.
+    pub fn alloc_memmap(&mut self, boot_info: &BootInfo, memory_map_entries: usize) {
+        if self.requests.memmap.is_some() {
+            self.memmap = Some(Memmap::new(boot_info, memory_map_entries));
+        }
+    }
+
+    pub fn base_revision(&self) -> u64 {
+        self.base_revision
+    }
+
+    // requests as-boot answers
+    pub fn request_count(&self) -> usize {
+        self.requests.count
+    }
+
+    pub fn exec_ranges(&self) -> Vec<(usize, usize)> {
+        let mut exec_ranges = vec![trampoline_range()];
+        if let Some(mp) = &self.mp {
+            exec_ranges.push(mp.ap_startup.exec_range());
+        }
+        exec_ranges
+    }
+
+    pub fn map_hhdm(&self, page_table: &mut PageTable, size: usize) -> Result<(), &'static str> {
+        page_table.map_huge_range(
+            HHDM_OFFSET,
+            0,
+            size,
+            PageTable::WRITABLE | PageTable::NO_EXECUTE,
+        )
+    }
+
+    // after exit_boot_services: answers the memory map and MP requests
+    pub fn finish(&mut self, memory_map: &[MemoryRegion], page_table: &PageTable) {
+        if let (Some(request), Some(memmap)) = (self.requests.memmap, &mut self.memmap) {
+            memmap.fill(memory_map);
+            log!(LogLevel::Info, "LIMINE: {} memory map entries", memmap.len);
+            set_response(request, memmap.response as *const MemmapResponse);
+        }
+        if let (Some(request), Some(mp)) = (self.requests.mp, &mut self.mp) {
+            mp.start(page_table.cr3(), self.gdtr);
+            set_response(request, mp.response as *const MpResponse);
+        }
+    }
+
+    pub unsafe fn jump_to_kernel(
+        &self,
+        entry_point: usize,
+        page_table: &PageTable,
+        stack: &KernelStack,
+    ) -> ! {
+        let gdtr_addr = (self.gdtr as *const Gdtr).addr();
+        let stack_top = hhdm(stack.top() as *const u8) as usize;
+
+        PageTable::enable_protection();
+        unsafe { limine_trampoline(gdtr_addr, page_table.cr3(), stack_top, entry_point) }
+    }
+
+    fn respond_framebuffer(&self, request: *mut Request, boot_info: &BootInfo) {
+        let frame_buffer = &boot_info.frame_buffer;
+        // (red, green, blue) shifts within a 32bit pixel
+        let shifts = match frame_buffer.format {
+            PixelFormat::Rgb => (0, 8, 16),
+            PixelFormat::Bgr => (16, 8, 0),
+            PixelFormat::Unknown => {
+                log!(LogLevel::Warn, "LIMINE: no usable framebuffer");
+                return;
+            }
+        };
+
+        let framebuffer = leak(Framebuffer {
+            address: hhdm(frame_buffer.base as usize as *const u8),
+            width: frame_buffer.width,
+            height: frame_buffer.height,
+            pitch: frame_buffer.stride * 4,
+            bpp: 32,
+            memory_model: FRAMEBUFFER_RGB,
+            red_mask_size: 8,
+            red_mask_shift: shifts.0,
+            green_mask_size: 8,
+            green_mask_shift: shifts.1,
+            blue_mask_size: 8,
+            blue_mask_shift: shifts.2,
+            unused: [0; 7],
+            edid_size: 0,
+            edid: 0,
+            mode_count: 0,
+            modes: 0,
+        });
+        let framebuffers = leak([hhdm(framebuffer)]);
+        respond(
+            request,
+            FramebufferResponse {
+                revision: 0,
+                framebuffer_count: 1,
+                framebuffers: hhdm(framebuffers.as_ptr()),
+            },
+        );
+    }
+
+    fn respond_modules(&self, request: *mut Request, boot_info: &BootInfo) {
+        // boot_info is the one main.rs filled in
+        let modules = unsafe { boot_info.modules() };
+        let pointers: &'static mut [u64] = Vec::leak(vec![0; modules.len()]);
+        for (pointer, module) in pointers.iter_mut().zip(modules) {
+            let file = leak(ModuleFile {
+                revision: 0,
+                address: hhdm(module.base as usize as *const u8),
+                size: module.size,
+                path: leak_c_str(unsafe { module.name() }),
+                cmdline: leak_c_str(""),
+                media_type: 0,
+                unused: 0,
+                tftp_ip: 0,
+                tftp_port: 0,
+                partition_index: 0,
+                mbr_disk_id: 0,
+                gpt_disk_uuid: [0; 16],
+                gpt_part_uuid: [0; 16],
+                part_uuid: [0; 16],
+            });
+            *pointer = hhdm(file);
+        }
+
+        respond(
+            request,
+            ModuleResponse {
+                revision: 0,
+                module_count: modules.len() as u64,
+                modules: hhdm(pointers.as_ptr()),
+            },
+        );
+    }
+}
+
+// Built from the final memory map, so all storage is allocated up front.
+#[derive(Debug)]
+struct Memmap {
+    response: &'static mut MemmapResponse,
+    entries: &'static mut [MemmapEntry],
+    pointers: &'static mut [u64],
+    len: usize,
+    // (base, end, kind) carved out of the regions they lie in, sorted by base
+    carve_outs: Vec<(u64, u64, u64)>,
+    frame_buffer: (u64, u64),
+}
+
+impl Memmap {
+    fn new(boot_info: &BootInfo, memory_map_entries: usize) -> Self {
+        let page_end =
+            |base: u64, size: u64| base + PageBox::pages_for(size as usize) as u64 * 4096;
+
+        let mut carve_outs = Vec::new();
+        let kernel = &boot_info.kernel;
+        carve_outs.push((
+            kernel.physical_base,
+            page_end(kernel.physical_base, kernel.size),
+            MEMMAP_KERNEL_AND_MODULES,
+        ));
+        for module in unsafe { boot_info.modules() } {
+            carve_outs.push((
+                module.base,
+                page_end(module.base, module.size),
+                MEMMAP_KERNEL_AND_MODULES,
+            ));
+        }
+        let frame_buffer = &boot_info.frame_buffer;
+        let frame_buffer = (
+            frame_buffer.base,
+            page_end(
+                frame_buffer.base,
+                frame_buffer.stride * frame_buffer.height * 4,
+            ),
+        );
+        carve_outs.push((frame_buffer.0, frame_buffer.1, MEMMAP_FRAMEBUFFER));
+        carve_outs.sort_unstable_by_key(|carve_out| carve_out.0);
+
+        // every carve-out can split one entry into three
+        let capacity = memory_map_entries + 2 * carve_outs.len() + 1;
+        let zero = MemmapEntry {
+            base: 0,
+            length: 0,
+            kind: 0,
+        };
+        Self {
+            response: leak(MemmapResponse {
+                revision: 0,
+                entry_count: 0,
+                entries: 0,
+            }),
+            entries: Vec::leak(vec![zero; capacity]),
+            pointers: Vec::leak(vec![0; capacity]),
+            len: 0,
+            carve_outs: carve_outs,
+            frame_buffer: frame_buffer,
+        }
+    }
+
+    fn fill(&mut self, memory_map: &[MemoryRegion]) {
+        let mut frame_buffer_found = false;
+        for region in memory_map {
+            let Some(kind) = Self::kind(region.kind) else {
+                continue;
+            };
+
+            let mut base = region.base;
+            let end = region.end();
+            for index in 0..self.carve_outs.len() {
+                let (carve_base, carve_end, carve_kind) = self.carve_outs[index];
+                if carve_end <= base || end <= carve_base {
+                    continue;
+                }
+                if base < carve_base {
+                    self.push(base, carve_base, kind);
+                }
+                let carved_end = carve_end.min(end);
+                self.push(carve_base.max(base), carved_end, carve_kind);
+                frame_buffer_found |= carve_kind == MEMMAP_FRAMEBUFFER;
+                base = carved_end;
+            }
+            if base < end {
+                self.push(base, end, kind);
+            }
+        }
+
+        // the framebuffer is usually MMIO, which is not in the memory map at all
+        let (frame_buffer_base, frame_buffer_end) = self.frame_buffer;
+        if !frame_buffer_found && frame_buffer_base < frame_buffer_end {
+            self.push(frame_buffer_base, frame_buffer_end, MEMMAP_FRAMEBUFFER);
+            self.entries[..self.len].sort_unstable_by_key(|entry| entry.base);
+        }
+
+        for index in 0..self.len {
+            self.pointers[index] = hhdm(&self.entries[index]);
+        }
+        self.response.entry_count = self.len as u64;
+        self.response.entries = hhdm(self.pointers.as_ptr());
+    }
+
+    fn push(&mut self, base: u64, end: u64, kind: u64) {
+        if let Some(last) = self.entries[..self.len].last_mut() {
+            if last.kind == kind && last.base + last.length == base {
+                last.length += end - base;
+                return;
+            }
+        }
+        if self.len == self.entries.len() {
+            log!(
+                LogLevel::Warn,
+                "LIMINE: memory map is full, dropping {:#x}-{:#x}",
+                base,
+                end
+            );
+            return;
+        }
+
+        self.entries[self.len] = MemmapEntry {
+            base: base,
+            length: end - base,
+            kind: kind,
+        };
+        self.len += 1;
+    }
+
+    // MMIO is left out, like Limine does
+    fn kind(kind: MemoryRegionKind) -> Option<u64> {
+        match kind {
+            MemoryRegionKind::Usable | MemoryRegionKind::Reclaimable => Some(MEMMAP_USABLE),
+            MemoryRegionKind::Loader => Some(MEMMAP_BOOTLOADER_RECLAIMABLE),
+            MemoryRegionKind::AcpiReclaimable => Some(MEMMAP_ACPI_RECLAIMABLE),
+            MemoryRegionKind::AcpiNvs => Some(MEMMAP_ACPI_NVS),
+            MemoryRegionKind::Runtime | MemoryRegionKind::Reserved => Some(MEMMAP_RESERVED),
+            MemoryRegionKind::Mmio => None,
+        }
+    }
+}
+
+// The enabled CPUs MP services reported in `BootInfo::cpus` are started after
+// exit_boot_services and wait in limine_ap_park.
+#[derive(Debug)]
+struct Mp {
+    response: &'static mut MpResponse,
+    lapic: Lapic,
+    cpus: &'static mut [MpInfo],
+    pointers: &'static mut [u64],
+    // physical stack top of each AP, 0 for the BSP
+    stack_tops: Vec<usize>,
+    ap_startup: ApStartup,
+}
+
+impl Mp {
+    fn new(boot_info: &BootInfo) -> Option<Self> {
+        let lapic = Lapic::current();
+        // BootInfo::cpus is indexed by MP services processor number
+        let cpus = unsafe { boot_info.cpus() };
+        let enabled_cpus: Vec<(usize, &CpuInfo)> = cpus
+            .iter()
+            .enumerate()
+            .filter(|(_, cpu)| cpu.is_enabled())
+            .collect();
+        if enabled_cpus.is_empty() {
+            log!(
+                LogLevel::Warn,
+                "LIMINE: no CPUs from MP services, not answering the MP request"
+            );
+            return None;
+        }
+        let ap_startup = match ApStartup::new() {
+            Ok(ap_startup) => ap_startup,
+            Err(error) => {
+                log!(
+                    LogLevel::Warn,
+                    "LIMINE: no memory for the AP trampoline: {}",
+                    error
+                );
+                return None;
+            }
+        };
+
+        let bsp_lapic_id = lapic.id();
+        let mut cpus = Vec::with_capacity(enabled_cpus.len());
+        let mut stack_tops = Vec::with_capacity(enabled_cpus.len());
+        for &(processor_number, cpu) in &enabled_cpus {
+            // MP services has no ACPI processor UID, the processor number stands in for it
+            cpus.push(MpInfo {
+                processor_id: processor_number as u32,
+                lapic_id: cpu.apic_id,
+                reserved: 0,
+                goto_address: 0,
+                extra_argument: 0,
+            });
+            if cpu.apic_id == bsp_lapic_id {
+                stack_tops.push(0);
+            } else {
+                let stack: &'static mut [u8] = PageBox::new(AP_STACK_PAGES).leak();
+                stack_tops.push(stack.as_ptr().addr() + stack.len());
+            }
+        }
+
+        Some(Self {
+            response: leak(MpResponse {
+                revision: 0,
+                flags: if lapic == Lapic::X2Apic { MP_X2APIC } else { 0 },
+                bsp_lapic_id: bsp_lapic_id,
+                cpu_count: 0,
+                cpus: 0,
+            }),
+            lapic: lapic,
+            pointers: Vec::leak(vec![0; cpus.len()]),
+            cpus: Vec::leak(cpus),
+            stack_tops: stack_tops,
+            ap_startup: ap_startup,
+        })
+    }
+
+    // CPUs that do not come up are left out of the response
+    fn start(&mut self, cr3: usize, gdtr: &Gdtr) {
+        let mut count = 0;
+        for (cpu, &stack_top) in self.cpus.iter_mut().zip(&self.stack_tops) {
+            if stack_top != 0 {
+                let started = unsafe {
+                    self.ap_startup.start(
+                        self.lapic,
+                        cpu.lapic_id,
+                        cr3,
+                        gdtr,
+                        hhdm(stack_top as *const u8) as usize,
+                        limine_ap_park as *const () as usize,
+                        hhdm(cpu) as usize,
+                    )
+                };
+                if !started {
+                    log!(
+                        LogLevel::Warn,
+                        "LIMINE: CPU with local APIC {} did not start",
+                        cpu.lapic_id
+                    );
+                    continue;
+                }
+            }
+            self.pointers[count] = hhdm(cpu);
+            count += 1;
+        }
+
+        log!(
+            LogLevel::Info,
+            "LIMINE: {} of {} CPUs running, BSP local APIC {}",
+            count,
+            self.cpus.len(),
+            self.response.bsp_lapic_id
+        );
+        self.response.cpu_count = count as u64;
+        self.response.cpus = hhdm(self.pointers.as_ptr());
+    }
+}
+
+fn hhdm<T>(ptr: *const T) -> u64 {
+    (HHDM_OFFSET + ptr.addr()) as u64
+}
+
+fn leak<T>(value: T) -> &'static mut T {
+    Box::leak(Box::new(value))
+}
+
+fn leak_c_str(s: &str) -> u64 {
+    let mut bytes = Vec::with_capacity(s.len() + 1);
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.push(0);
+    hhdm(Vec::leak(bytes).as_ptr())
+}
+
+fn respond<T: 'static>(request: *mut Request, response: T) {
+    set_response(request, leak(response));
+}
+
+fn set_response<T>(request: *mut Request, response: *const T) {
+    unsafe { ptr::write_volatile(&raw mut (*request).response, hhdm(response)) };
+}
+
+// BSP: limine_trampoline(gdtr, pml4, stack_top, entry_point) loads the page table and GDT,
+// reloads the segments and enters the kernel with the registers cleared.
+// APs: limine_ap_park(info) spins until the kernel writes goto_address into info,
+// then calls it with info in rdi.
+global_asm!(
+    ".global limine_trampoline",
+    ".global limine_ap_park",
+    ".global limine_trampoline_end",
+    "limine_trampoline:",
+    "cli",
+    "cld",
+    "mov cr3, rsi",
+    "lgdt [rdi]",
+    "mov rsp, rdx",
+    "push {code64}",
+    "lea rax, [rip + 2f]",
+    "push rax",
+    "retfq",
+    "2:",
+    "mov ax, {data64}",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    "xor edi, edi",
+    "jmp 4f",
+    "limine_ap_park:",
+    "3:",
+    "pause",
+    "mov rcx, [rdi + {goto_address}]",
+    "test rcx, rcx",
+    "jz 3b",
+    "4:",
+    "xor eax, eax",
+    "xor ebx, ebx",
+    "xor edx, edx",
+    "xor esi, esi",
+    "xor ebp, ebp",
+    "xor r8d, r8d",
+    "xor r9d, r9d",
+    "xor r10d, r10d",
+    "xor r11d, r11d",
+    "xor r12d, r12d",
+    "xor r13d, r13d",
+    "xor r14d, r14d",
+    "xor r15d, r15d",
+    "push rbp",
+    "jmp rcx",
+    "limine_trampoline_end:",
+    code64 = const smp::CODE64_SELECTOR,
+    data64 = const smp::DATA64_SELECTOR,
+    goto_address = const offset_of!(MpInfo, goto_address),
+);
+
+unsafe extern "sysv64" {
+    fn limine_trampoline(gdtr: usize, pml4: usize, stack_top: usize, entry_point: usize) -> !;
+    fn limine_ap_park();
+    fn limine_trampoline_end();
+}
+
+fn trampoline_range() -> (usize, usize) {
+    (
+        limine_trampoline as *const () as usize,
+        limine_trampoline_end as *const () as usize,
+    )
+}
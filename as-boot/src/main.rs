@@ -1,58 +1,492 @@
-#![no_main]
+#![cfg_attr(not(test), no_main)]
 #![no_std]
 
+extern crate alloc;
+
 #[macro_use]
 mod efi_wrapper;
+mod boot_vars;
+mod config;
+mod cpus;
+mod error;
+mod firmware_tables;
+mod handoff;
+mod kaslr;
+mod limine;
+mod memory_regions;
+mod menu;
+mod modules;
+mod paging;
+mod random;
+mod runtime;
+mod serial;
+mod smp;
+mod verify;
 
+use alloc::vec;
 use bootgfx::Color;
 use bootgfx::terminal::Terminal;
+use bootinfo::BootSlice;
+use bootinfo::KernelInfo;
+use config::Config;
+use config::Entry;
+use core::slice;
 use efi::EFI_STATUS_SUCCESS;
 use efi::EfiHandle;
 use efi::EfiStatus;
 use efi::EfiSystemTable;
+use efi::status_to_result;
 use efi_wrapper::File;
+use efi_wrapper::GraphicsMode;
+use efi_wrapper::LogLevel;
 use efi_wrapper::MemoryMap;
 use efi_wrapper::PageBox;
 use efi_wrapper::set_terminal;
 use elf::Elf64;
+use elf::Elf64Phdr;
+use error::BootResult;
+use error::Context;
+use handoff::KernelStack;
+use inflate::Gzip;
+use limine::LimineBoot;
+use memory_regions::MemoryRegions;
+use paging::PageTable;
 
-pub fn main() -> Result<(), &'static str> {
+pub fn main() -> BootResult<()> {
     println!("Hello, World!");
     println!("as-boot alpha version");
 
-    let kernel = Kernel::new("kernel.elf")?;
-    println!("KERNEL: {:?}", kernel);
+    let mut config = Config::load();
+    efi_wrapper::set_log_level(config.log_level);
+    if let Some(log_file) = &config.log_file {
+        efi_wrapper::set_log_file(log_file);
+    }
+    if !config.diagnostics.is_empty() {
+        println!(
+            "CONFIG: {} problem(s) in {}, press any key to continue",
+            config.diagnostics.len(),
+            Config::PATH
+        );
+        efi_wrapper::wait_for_key(Some(CONFIG_DIAGNOSTICS_TIMEOUT_MS));
+    }
+
+    match boot_vars::bump_boot_count() {
+        Ok(count) => log!(LogLevel::Info, "NVRAM: boot count {}", count),
+        Err(error) => log!(
+            LogLevel::Warn,
+            "NVRAM: failed to update boot count: {}",
+            error
+        ),
+    }
+    let secure_boot = boot_vars::secure_boot_state();
+    log!(
+        LogLevel::Info,
+        "SECUREBOOT: {}, setup mode {}",
+        if secure_boot.secure_boot {
+            "enabled"
+        } else {
+            "disabled"
+        },
+        secure_boot.setup_mode
+    );
+    boot_vars::log_variables();
+    log_volumes();
+    if let Some(title) = boot_vars::take_boot_once() {
+        match config.find_entry(&title) {
+            Some(index) => {
+                log!(LogLevel::Info, "NVRAM: booting `{}` once", title);
+                config.default = index;
+                config.timeout = 0;
+            }
+            None => log!(
+                LogLevel::Warn,
+                "NVRAM: boot once entry `{}` does not exist",
+                title
+            ),
+        }
+    }
+
+    let mut timeout = Some(config.timeout);
+    let entry = loop {
+        let entry = menu::run(&config, timeout);
+        let Some(path) = &entry.efi else {
+            break entry;
+        };
+        chainload(path, &entry.cmdline);
+        // back from the application, stay in the menu
+        timeout = None;
+    };
+    setup_graphics(config.resolution)?;
+    log!(
+        LogLevel::Info,
+        "ENTRY: {}, kernel {}, cmdline \"{}\", resolution {:?}",
+        entry.title,
+        entry.kernel,
+        entry.cmdline,
+        config.resolution
+    );
+    for module in &entry.modules {
+        log!(LogLevel::Info, "ENTRY: module {}", module);
+    }
+
+    let mut kernel = Kernel::new(&entry)?;
+    log!(
+        LogLevel::Info,
+        "KERNEL: buff {:#x}, virtual {:#x}, size {:#x}, entry {:#x}",
+        kernel.kernel_buff_addr,
+        kernel.kernel_virtual_addr,
+        kernel.kernel_size,
+        kernel.entry_point
+    );
+    if let Some(addr) = kernel.requested_physical_addr {
+        log!(
+            LogLevel::Info,
+            "KERNEL: requested physical {:#x}, {}",
+            addr,
+            if addr == kernel.kernel_buff_addr {
+                "placed there"
+            } else {
+                "relocated"
+            }
+        );
+    }
+    for segment in kernel.segments() {
+        log!(LogLevel::Debug, "KERNEL SEGMENT: {:?}", segment);
+    }
+    let limine_requests = limine::Requests::scan(kernel.image_mut());
+
+    let boot_info = handoff::alloc_boot_info();
+    boot_info.frame_buffer = handoff::frame_buffer_info(
+        &efi_wrapper::get_frame_buffer().context("failed to get frame buffer")?,
+    );
+    boot_info.kernel = KernelInfo {
+        physical_base: kernel.kernel_buff_addr as u64,
+        virtual_base: kernel.kernel_virtual_addr as u64,
+        size: kernel.kernel_size as u64,
+        slide: kernel.slide as u64,
+    };
+    boot_info.cmdline = BootSlice::from_slice(handoff::leak_bytes(entry.cmdline.as_bytes()));
+    boot_info.modules = modules::load_modules(&entry)?;
+
+    match firmware_tables::find_rsdp() {
+        Some(rsdp) => {
+            log!(
+                LogLevel::Info,
+                "ACPI: RSDP revision {} at {:#x}",
+                rsdp.revision,
+                rsdp.addr
+            );
+            boot_info.rsdp = rsdp.addr as u64;
+        }
+        None => log!(LogLevel::Warn, "ACPI: RSDP not found"),
+    }
+    match firmware_tables::find_smbios() {
+        Some(smbios) => {
+            log!(
+                LogLevel::Info,
+                "SMBIOS: version {}.{} at {:#x}",
+                smbios.major_version,
+                smbios.minor_version,
+                smbios.addr
+            );
+            boot_info.smbios = smbios.addr as u64;
+        }
+        None => log!(LogLevel::Warn, "SMBIOS: entry point not found"),
+    }
+    match cpus::discover() {
+        Ok(cpus) => boot_info.cpus = cpus,
+        Err(error) => log!(LogLevel::Warn, "CPU: {}, reporting no processors", error),
+    }
+
+    let mut limine = limine_requests.map(|requests| LimineBoot::new(requests, boot_info));
+    if let Some(limine) = &limine {
+        log!(
+            LogLevel::Info,
+            "LIMINE: booting with the Limine protocol, base revision {}, {} requests",
+            limine.base_revision(),
+            limine.request_count()
+        );
+    }
+
+    let memory_map = MemoryMap::get_memory_map().context("failed to get memory map")?;
+    let frame_buffer_end = boot_info.frame_buffer.base
+        + boot_info.frame_buffer.stride * boot_info.frame_buffer.height * 4;
+    let identity_map_size = MIN_IDENTITY_MAP_SIZE
+        .max(memory_regions::max_physical_address(&memory_map))
+        .max(frame_buffer_end as usize);
+    drop(memory_map);
+
+    let mut exec_ranges = vec![handoff::trampoline_range()];
+    if let Some(limine) = &limine {
+        exec_ranges.extend(limine.exec_ranges());
+    }
+    let mut page_table = PageTable::new();
+    page_table.identity_map(identity_map_size, &exec_ranges)?;
+    if let Some(limine) = &limine {
+        limine.map_hhdm(&mut page_table, identity_map_size)?;
+    }
+    kernel.map_to(&mut page_table)?;
+    page_table.reserve(runtime::PAGE_TABLE_RESERVE);
+    let memory_attributes = runtime::MemoryAttributes::new();
+    let stack = KernelStack::new();
+    // sized after everything else is allocated, so only the few allocations
+    // left before exit_boot_services can split descriptors
+    let memory_map = MemoryMap::get_memory_map().context("failed to get memory map")?;
+    let mut memory_regions = MemoryRegions::new(&memory_map);
+    drop(memory_map);
+    if let Some(limine) = &mut limine {
+        limine.alloc_memmap(boot_info, memory_regions.capacity());
+    }
 
     println!("Hello, TERMINAL!");
+    if let Err(error) = efi_wrapper::write_log_file() {
+        log!(LogLevel::Warn, "LOG: failed to write log file: {}", error);
+    }
 
-    let memory_map = unsafe { efi_wrapper::exit_boot_services() };
+    let mut memory_map =
+        unsafe { efi_wrapper::exit_boot_services() }.context("failed to exit boot services")?;
+    memory_regions.fill(&memory_map)?;
+    boot_info.memory_map = BootSlice::from_slice(memory_regions.as_slice());
+    match runtime::set_virtual_address_map(
+        &mut memory_map,
+        &mut page_table,
+        memory_attributes.as_ref(),
+    ) {
+        Ok(runtime_services) => {
+            boot_info.runtime_services = runtime_services.unwrap_or(0) as u64;
+            log!(
+                LogLevel::Info,
+                "RUNTIME: runtime services at {:#x}",
+                boot_info.runtime_services
+            );
+        }
+        Err(error) => log!(
+            LogLevel::Warn,
+            "RUNTIME: failed to set virtual address map: {}",
+            error
+        ),
+    }
+    if let Some(limine) = &mut limine {
+        limine.finish(memory_regions.as_slice(), &page_table);
+    }
     println!("Hello, Freedom!");
+    // printed regardless of the log level, as-build test looks for this line
+    println!("BOOT: jumping to kernel entry {:#x}", kernel.entry_point);
+
+    if let Some(limine) = &limine {
+        unsafe { limine.jump_to_kernel(kernel.entry_point, &page_table, &stack) }
+    }
+    unsafe { handoff::jump_to_kernel(kernel.entry_point, &page_table, &stack, boot_info) }
+}
+
+fn log_volumes() {
+    let boot_volume = efi_wrapper::boot_volume().ok();
+    let Ok(volumes) = efi_wrapper::volumes() else {
+        return;
+    };
+    for (index, volume) in volumes.iter().enumerate() {
+        log!(
+            LogLevel::Info,
+            "VOLUME: fs{}: handle {:?}{}",
+            index,
+            volume.handle(),
+            if Some(volume) == boot_volume {
+                " (boot)"
+            } else {
+                ""
+            }
+        );
+    }
 
-    loop {}
+    let Ok(mut root) = File::new("\\") else {
+        return;
+    };
+    for file_info in root.read_dir().flatten() {
+        let time = &file_info.modification_time;
+        log!(
+            LogLevel::Debug,
+            "VOLUME: \\{}{} {} bytes, modified {:04}-{:02}-{:02} {:02}:{:02}",
+            &*file_info.name,
+            if file_info.is_directory() { "\\" } else { "" },
+            file_info.size,
+            time.year,
+            time.month,
+            time.day,
+            time.hour,
+            time.minute
+        );
+    }
+}
+
+fn chainload(path: &str, load_options: &str) {
+    log!(
+        LogLevel::Info,
+        "CHAINLOAD: starting {}, load options \"{}\"",
+        path,
+        load_options
+    );
+    let exit = File::load(path)
+        .context("failed to load the application")
+        .and_then(|(page_box, size)| {
+            verify::verify_file(path, &page_box[..size])?;
+            efi_wrapper::start_image(path, &page_box[..size], load_options)
+                .context("failed to start the application")
+        });
+    match exit {
+        Ok(exit) => match status_to_result(exit.status) {
+            Ok(()) => log!(LogLevel::Info, "CHAINLOAD: {} exited", path),
+            Err(error) => println!("CHAINLOAD: {} exited with {} {}", path, error, exit.message),
+        },
+        Err(error) => println!("CHAINLOAD: {}: {}", path, error),
+    }
+
+    // the application may have switched graphics modes
+    if let Ok(frame_buffer) = efi_wrapper::get_frame_buffer() {
+        set_terminal(Terminal::new(frame_buffer));
+    }
+    println!("Press any key to return to the boot menu");
+    efi_wrapper::wait_for_key(Some(CHAINLOAD_RETURN_TIMEOUT_MS));
+}
+
+fn setup_graphics(resolution: Option<(usize, usize)>) -> BootResult<()> {
+    let modes = efi_wrapper::graphics_modes().context("failed to locate graphics output")?;
+    for mode in modes {
+        log!(
+            LogLevel::Debug,
+            "GRAPHICS: mode {}: {}x{}, stride {}, {:?}",
+            mode.number,
+            mode.width,
+            mode.height,
+            mode.stride,
+            mode.pixel_format
+        );
+    }
+
+    let current = efi_wrapper::current_graphics_mode().context("failed to query graphics mode")?;
+    let Some(mode) = select_graphics_mode(resolution) else {
+        log!(
+            LogLevel::Warn,
+            "GRAPHICS: no drawable mode found, keeping mode {}",
+            current.number
+        );
+        return Ok(());
+    };
+
+    if mode.number != current.number {
+        efi_wrapper::set_graphics_mode(&mode).context("failed to set graphics mode")?;
+        let frame_buffer = efi_wrapper::get_frame_buffer().context("failed to get frame buffer")?;
+        set_terminal(Terminal::new(frame_buffer));
+    } else {
+        efi_wrapper::clear_terminal();
+    }
+
+    log!(
+        LogLevel::Info,
+        "GRAPHICS: using mode {}: {}x{}, stride {}, {:?} (requested {:?})",
+        mode.number,
+        mode.width,
+        mode.height,
+        mode.stride,
+        mode.pixel_format,
+        resolution
+    );
+    Ok(())
+}
+
+// exact match, then the largest mode that fits in the requested resolution, then the largest mode
+fn select_graphics_mode(resolution: Option<(usize, usize)>) -> Option<GraphicsMode> {
+    let modes = || {
+        efi_wrapper::graphics_modes()
+            .into_iter()
+            .flatten()
+            .filter(|mode| mode.is_drawable())
+    };
+    let largest = modes().max_by_key(|mode| mode.pixels());
+
+    let Some((width, height)) = resolution else {
+        return largest;
+    };
+
+    modes()
+        .find(|mode| mode.width == width && mode.height == height)
+        .or_else(|| {
+            modes()
+                .filter(|mode| mode.width <= width && mode.height <= height)
+                .max_by_key(|mode| mode.pixels())
+        })
+        .or(largest)
+}
+
+const CONFIG_DIAGNOSTICS_TIMEOUT_MS: usize = 10_000;
+const CHAINLOAD_RETURN_TIMEOUT_MS: usize = 10_000;
+const MIN_IDENTITY_MAP_SIZE: usize = 4 * 1024 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct KernelSegment {
+    virtual_addr: usize,
+    physical_addr: usize,
+    size: usize,
+    writable: bool,
+    executable: bool,
+}
+
+impl KernelSegment {
+    const EMPTY: Self = Self {
+        virtual_addr: 0,
+        physical_addr: 0,
+        size: 0,
+        writable: false,
+        executable: false,
+    };
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Kernel {
     kernel_buff_addr: usize,
+    // physical address asked for by the ELF, None if it may be loaded anywhere
+    requested_physical_addr: Option<usize>,
+    // distance from the link address, see kaslr.rs
+    slide: usize,
     kernel_virtual_addr: usize,
+    kernel_size: usize,
     entry_point: usize,
+    segments: [KernelSegment; Kernel::MAX_SEGMENTS],
+    segment_count: usize,
 }
 
 impl Kernel {
-    pub fn new(path: &str) -> Result<Kernel, &'static str> {
-        let (kernel_tmp_pagebox, kernel_tmp_buff_size) = Self::load_kernel_to_tmp_buffer(path)?;
+    const MAX_SEGMENTS: usize = 16;
+
+    pub fn new(entry: &Entry) -> BootResult<Kernel> {
+        let (kernel_tmp_pagebox, kernel_tmp_buff_size) =
+            File::load(&entry.kernel).context("failed to load kernel")?;
         let kernel_temp_buff: &[u8] = &kernel_tmp_pagebox[0..kernel_tmp_buff_size];
-        Self::expand_kernel(kernel_temp_buff)
-    }
+        // the digest and signature cover the file as stored, compressed or not
+        verify::verify_kernel(entry, kernel_temp_buff)?;
 
-    fn load_kernel_to_tmp_buffer(path: &str) -> Result<(PageBox, usize), &'static str> {
-        let mut file = File::new(path)?;
-        let file_size = file.size();
-        let mut page_box = PageBox::new_from_bytes(file_size);
-        let load_size = file.read(&mut *page_box)?;
-        assert_eq!(file_size, load_size);
+        if Gzip::is_gzip(kernel_temp_buff) {
+            let (decompressed_pagebox, decompressed_size) = Self::decompress(kernel_temp_buff)?;
+            return Ok(Self::expand_kernel(
+                &decompressed_pagebox[0..decompressed_size],
+            )?);
+        }
+        Ok(Self::expand_kernel(kernel_temp_buff)?)
+    }
 
-        Ok((page_box, load_size))
+    fn decompress(kernel_temp_buff: &[u8]) -> Result<(PageBox, usize), &'static str> {
+        let gzip = Gzip::new(kernel_temp_buff)?;
+        if gzip.decompressed_size() == 0 {
+            return Err("compressed kernel is empty");
+        }
+        let mut page_box = PageBox::new_from_bytes(gzip.decompressed_size());
+        let size = gzip.decompress(&mut page_box)?;
+        log!(
+            LogLevel::Info,
+            "KERNEL: decompressed {} bytes of gzip into {} bytes",
+            kernel_temp_buff.len(),
+            size
+        );
+        Ok((page_box, size))
     }
 
     fn expand_kernel(kernel_temp_buff: &[u8]) -> Result<Kernel, &'static str> {
@@ -60,20 +494,143 @@ impl Kernel {
         let expand_info = elf64.expand_info()?;
         let expand_size = (expand_info.upper_addr - expand_info.lower_addr) as usize;
 
-        let kernel_virtual_addr = expand_info.lower_addr as usize;
-        let entry_point = elf64.entry()? as usize;
+        let link_addr = expand_info.lower_addr as usize;
 
-        let kernel_buff_pagebox = PageBox::new_from_bytes(expand_size);
+        let requested_physical_addr =
+            Self::requested_physical_addr(&elf64, expand_info.lower_addr)?;
+        let kernel_buff_pagebox = match requested_physical_addr {
+            Some(addr) => match PageBox::new_at(addr, PageBox::pages_for(expand_size)) {
+                Ok(page_box) => page_box,
+                Err(error) => {
+                    log!(
+                        LogLevel::Warn,
+                        "KERNEL: cannot load at physical {:#x}: {}, loading anywhere",
+                        addr,
+                        error
+                    );
+                    PageBox::new_from_bytes(expand_size)
+                }
+            },
+            None => PageBox::new_from_bytes(expand_size),
+        };
         let kernel_buff: &mut [u8] = kernel_buff_pagebox.leak();
         elf64.expand(kernel_buff)?;
         let kernel_buff_addr = kernel_buff.as_ptr().addr();
 
+        // a PIE kernel has to be relocated even when it stays at its link address
+        let slide = if elf64.is_position_independent()? {
+            let slide = kaslr::choose_slide(link_addr, expand_size)?;
+            elf64.relocate(kernel_buff, slide as u64)?;
+            slide
+        } else {
+            0
+        };
+        let kernel_virtual_addr = link_addr + slide;
+        let entry_point = elf64.entry()? as usize + slide;
+
+        let mut segments = [KernelSegment::EMPTY; Self::MAX_SEGMENTS];
+        let mut segment_count = 0;
+        for phdr in elf64.program_headers()? {
+            if phdr.p_type() != Elf64Phdr::PT_LOAD || phdr.p_memsz() == 0 {
+                continue;
+            }
+            if segment_count == Self::MAX_SEGMENTS {
+                return Err("too many kernel segments");
+            }
+
+            let link_virtual_addr = phdr.p_vaddr() as usize;
+            segments[segment_count] = KernelSegment {
+                virtual_addr: link_virtual_addr + slide,
+                physical_addr: kernel_buff_addr + (link_virtual_addr - link_addr),
+                size: phdr.p_memsz() as usize,
+                writable: phdr.w_flag(),
+                executable: phdr.x_flag(),
+            };
+            segment_count += 1;
+        }
+
         Ok(Kernel {
             kernel_buff_addr: kernel_buff_addr,
+            requested_physical_addr: requested_physical_addr,
+            slide: slide,
             kernel_virtual_addr: kernel_virtual_addr,
+            kernel_size: expand_size,
             entry_point: entry_point,
+            segments: segments,
+            segment_count: segment_count,
         })
     }
+
+    // A kernel asks for a physical location by linking its segments with p_paddr != p_vaddr
+    // (`AT()` in the linker script). The image is loaded as one block, so every segment
+    // has to keep the same p_vaddr - p_paddr distance.
+    fn requested_physical_addr(
+        elf64: &Elf64,
+        lower_addr: u64,
+    ) -> Result<Option<usize>, &'static str> {
+        let mut offset = None;
+        for phdr in elf64.program_headers()? {
+            if phdr.p_type() != Elf64Phdr::PT_LOAD || phdr.p_memsz() == 0 {
+                continue;
+            }
+            if phdr.p_paddr() == phdr.p_vaddr() {
+                return Ok(None);
+            }
+
+            let segment_offset = phdr.p_vaddr().wrapping_sub(phdr.p_paddr());
+            if *offset.get_or_insert(segment_offset) != segment_offset {
+                log!(
+                    LogLevel::Warn,
+                    "KERNEL: segments are not contiguous in physical memory, loading anywhere"
+                );
+                return Ok(None);
+            }
+        }
+
+        let Some(offset) = offset else {
+            return Ok(None);
+        };
+        let addr = lower_addr.wrapping_sub(offset) as usize;
+        if addr % PageBox::PAGE_SIZE != 0 {
+            log!(
+                LogLevel::Warn,
+                "KERNEL: physical address {:#x} is not page aligned, loading anywhere",
+                addr
+            );
+            return Ok(None);
+        }
+        Ok(Some(addr))
+    }
+
+    // the expanded image, leaked by expand_kernel
+    pub fn image_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.kernel_buff_addr as *mut u8, self.kernel_size) }
+    }
+
+    pub fn segments(&self) -> &[KernelSegment] {
+        &self.segments[..self.segment_count]
+    }
+
+    pub fn map_to(&self, page_table: &mut PageTable) -> BootResult<()> {
+        for segment in self.segments() {
+            let mut flags = 0;
+            if segment.writable {
+                flags |= PageTable::WRITABLE;
+            }
+            if !segment.executable {
+                flags |= PageTable::NO_EXECUTE;
+            }
+
+            let page_offset = segment.virtual_addr % PageTable::PAGE_SIZE;
+            page_table.map_range(
+                segment.virtual_addr - page_offset,
+                segment.physical_addr - page_offset,
+                segment.size + page_offset,
+                flags,
+            )?;
+        }
+        Ok(())
+    }
 }
 
 #[unsafe(no_mangle)]
@@ -84,12 +641,13 @@ pub unsafe extern "efiapi" fn efi_main(
     unsafe {
         efi_wrapper::init(image_handle, system_table);
     }
+    efi_wrapper::init_serial();
     let frame_buffer = efi_wrapper::get_frame_buffer().expect("failed to get frame buffer");
     let terminal = Terminal::new(frame_buffer);
     set_terminal(terminal);
 
-    if let Err(msg) = main() {
-        panic!("ERROR: {}", msg);
+    if let Err(error) = main() {
+        panic!("ERROR: {}", error);
     } else {
         EFI_STATUS_SUCCESS
     }
@@ -0,0 +1,120 @@
+use crate::efi_wrapper::MemoryMap;
+use crate::efi_wrapper::PageBox;
+use bootinfo::MemoryRegion;
+use bootinfo::MemoryRegionKind;
+use core::slice;
+use efi::EfiMemoryDescriptor;
+use efi::EfiMemoryType;
+
+// Region array for the kernel. It is allocated while boot services are still available
+// and filled from the final memory map returned by exit_boot_services.
+#[derive(Debug)]
+pub struct MemoryRegions {
+    regions: &'static mut [MemoryRegion],
+    len: usize,
+}
+
+impl MemoryRegions {
+    // this buffer, the Limine memmap, the log file write and exit_boot_services retries
+    // may still grow the memory map a little
+    const SLACK_ENTRIES: usize = 32;
+
+    pub fn new(memory_map: &MemoryMap) -> Self {
+        let capacity = memory_map.entry_count() + Self::SLACK_ENTRIES;
+        let page_box = PageBox::new_from_bytes(capacity * size_of::<MemoryRegion>());
+        let buff: &'static mut [u8] = page_box.leak();
+        let capacity = buff.len() / size_of::<MemoryRegion>();
+        let regions =
+            unsafe { slice::from_raw_parts_mut(buff.as_mut_ptr() as *mut MemoryRegion, capacity) };
+
+        Self {
+            regions: regions,
+            len: 0,
+        }
+    }
+
+    pub fn fill(&mut self, memory_map: &MemoryMap) -> Result<(), &'static str> {
+        self.len = 0;
+
+        for descriptor in memory_map.iter() {
+            if descriptor.number_of_pages() == 0 {
+                continue;
+            }
+            if self.len == self.regions.len() {
+                return Err("too many memory regions");
+            }
+
+            self.regions[self.len] = MemoryRegion {
+                base: descriptor.physical_start(),
+                pages: descriptor.number_of_pages(),
+                kind: classify(&descriptor),
+            };
+            self.len += 1;
+        }
+
+        self.regions[..self.len].sort_unstable_by_key(|region| region.base);
+        self.merge();
+
+        Ok(())
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.regions.len()
+    }
+
+    pub fn as_slice(&self) -> &[MemoryRegion] {
+        &self.regions[..self.len]
+    }
+
+    fn merge(&mut self) {
+        if self.len == 0 {
+            return;
+        }
+
+        let mut merged_len = 1;
+        for i in 1..self.len {
+            let region = self.regions[i];
+            let last = &mut self.regions[merged_len - 1];
+            if last.kind == region.kind && last.end() == region.base {
+                last.pages += region.pages;
+            } else {
+                self.regions[merged_len] = region;
+                merged_len += 1;
+            }
+        }
+        self.len = merged_len;
+    }
+}
+
+pub fn classify(descriptor: &EfiMemoryDescriptor) -> MemoryRegionKind {
+    let Some(memory_type) = descriptor.memory_type() else {
+        return MemoryRegionKind::Reserved;
+    };
+
+    match memory_type {
+        EfiMemoryType::EfiConventionalMemory => MemoryRegionKind::Usable,
+        EfiMemoryType::EfiBootServicesCode | EfiMemoryType::EfiBootServicesData => {
+            MemoryRegionKind::Reclaimable
+        }
+        EfiMemoryType::EfiLoaderCode | EfiMemoryType::EfiLoaderData => MemoryRegionKind::Loader,
+        EfiMemoryType::EfiRuntimeServicesCode | EfiMemoryType::EfiRuntimeServicesData => {
+            MemoryRegionKind::Runtime
+        }
+        EfiMemoryType::EfiACPIReclaimMemory => MemoryRegionKind::AcpiReclaimable,
+        EfiMemoryType::EfiACPIMemoryNVS => MemoryRegionKind::AcpiNvs,
+        EfiMemoryType::EfiMemoryMappedIO | EfiMemoryType::EfiMemoryMappedIOPortSpace => {
+            MemoryRegionKind::Mmio
+        }
+        _ => MemoryRegionKind::Reserved,
+    }
+}
+
+// highest address backed by memory (not MMIO), which is what the identity mapping has to cover
+pub fn max_physical_address(memory_map: &MemoryMap) -> usize {
+    memory_map
+        .iter()
+        .filter(|descriptor| classify(descriptor) != MemoryRegionKind::Mmio)
+        .map(|descriptor| descriptor.physical_end() as usize)
+        .max()
+        .unwrap_or(0)
+}
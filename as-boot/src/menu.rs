@@ -0,0 +1,101 @@
+use crate::config::Config;
+use crate::config::Entry;
+use crate::efi_wrapper;
+use efi::EfiInputKey;
+
+// boots the default entry after `timeout` seconds without a key press, waits forever if None
+pub fn run(config: &Config, timeout: Option<usize>) -> Entry {
+    let entries = config.entries();
+    let mut selected = config.default;
+    let mut countdown = timeout;
+
+    efi_wrapper::reset_input();
+
+    loop {
+        draw(entries, selected, countdown);
+
+        let key = match countdown {
+            Some(0) => return entries[selected].clone(),
+            Some(seconds) => match efi_wrapper::wait_for_key(Some(1000)) {
+                Some(key) => {
+                    countdown = None;
+                    key
+                }
+                None => {
+                    countdown = Some(seconds - 1);
+                    continue;
+                }
+            },
+            None => match efi_wrapper::wait_for_key(None) {
+                Some(key) => key,
+                None => continue,
+            },
+        };
+
+        match (key.scan_code, key.unicode_char) {
+            (EfiInputKey::SCAN_UP, _) => selected = selected.saturating_sub(1),
+            (EfiInputKey::SCAN_DOWN, _) => {
+                if selected + 1 < entries.len() {
+                    selected += 1;
+                }
+            }
+            (_, EfiInputKey::CHAR_CARRIAGE_RETURN) => return entries[selected].clone(),
+            (_, c) if c == b'e' as u16 => {
+                if let Some(entry) = edit_cmdline(&entries[selected]) {
+                    return entry;
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn draw(entries: &[Entry], selected: usize, countdown: Option<usize>) {
+    efi_wrapper::clear_terminal();
+
+    println!("as-boot boot menu");
+    println!("");
+    for (index, entry) in entries.iter().enumerate() {
+        let marker = if index == selected { '>' } else { ' ' };
+        println!("{} {}", marker, entry.title);
+    }
+    println!("");
+    println!("Up/Down: select  Enter: boot  e: edit command line");
+    if let Some(seconds) = countdown {
+        println!("Booting `{}` in {}s", entries[selected].title, seconds);
+    }
+}
+
+// Enter boots the entry with the edited command line, Esc goes back to the menu
+fn edit_cmdline(entry: &Entry) -> Option<Entry> {
+    let mut entry = entry.clone();
+
+    loop {
+        efi_wrapper::clear_terminal();
+        println!("Edit command line of `{}`", entry.title);
+        println!("");
+        print!("> ");
+        print!("{}", entry.cmdline);
+        println!("");
+        println!("");
+        println!("Enter: boot  Esc: cancel  Backspace: delete");
+
+        let Some(key) = efi_wrapper::wait_for_key(None) else {
+            continue;
+        };
+        if key.scan_code == EfiInputKey::SCAN_ESC {
+            return None;
+        }
+
+        match key.unicode_char {
+            EfiInputKey::CHAR_CARRIAGE_RETURN => return Some(entry),
+            EfiInputKey::CHAR_BACKSPACE => {
+                entry.cmdline.pop();
+            }
+            c if (0x20..0x7f).contains(&c) => {
+                entry.cmdline.push(c as u8 as char);
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::config::Entry;
+use crate::efi_wrapper::File;
+use crate::efi_wrapper::LogLevel;
+use crate::efi_wrapper::PageBox;
+use crate::error::BootResult;
+use crate::error::Context;
+use crate::handoff;
+use crate::verify;
+use bootinfo::BootSlice;
+use bootinfo::Module;
+use core::slice;
+
+// Loads the modules of `entry` into pages that are kept after exit_boot_services.
+pub fn load_modules(entry: &Entry) -> BootResult<BootSlice<Module>> {
+    let paths = &entry.modules;
+    if paths.is_empty() {
+        return Ok(BootSlice::empty());
+    }
+
+    let buff: &'static mut [u8] = PageBox::new_from_bytes(paths.len() * size_of::<Module>()).leak();
+    let modules =
+        unsafe { slice::from_raw_parts_mut(buff.as_mut_ptr() as *mut Module, paths.len()) };
+
+    for (module, path) in modules.iter_mut().zip(paths) {
+        let (page_box, size) = match File::load(path) {
+            Ok(loaded) => loaded,
+            Err(error) => {
+                println!("MODULE: failed to load {}: {}", path, error);
+                return Err(error).context("failed to load module");
+            }
+        };
+        verify::verify_file(path, &page_box[..size])?;
+        let data: &'static mut [u8] = page_box.leak();
+
+        *module = Module {
+            base: data.as_ptr().addr() as u64,
+            size: size as u64,
+            name: BootSlice::from_slice(handoff::leak_bytes(path.as_bytes())),
+        };
+        log!(
+            LogLevel::Info,
+            "MODULE: {} at {:#x}, {} bytes",
+            path,
+            module.base,
+            module.size
+        );
+    }
+
+    Ok(BootSlice::from_slice(modules))
+}
@@ -0,0 +1,231 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::PageBox;
+use core::arch::asm;
+use core::mem;
+use core::slice;
+
+#[derive(Debug)]
+pub struct PageTable {
+    pml4: *mut u64,
+    // pages set aside for tables that are created after exit_boot_services
+    reserve: &'static mut [u8],
+}
+
+impl PageTable {
+    pub const PAGE_SIZE: usize = 4096;
+    pub const HUGE_PAGE_SIZE: usize = 512 * Self::PAGE_SIZE;
+
+    pub const PRESENT: u64 = 1 << 0;
+    pub const WRITABLE: u64 = 1 << 1;
+    pub const HUGE_PAGE: u64 = 1 << 7;
+    pub const NO_EXECUTE: u64 = 1 << 63;
+
+    const ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+    const ENTRIES: usize = 512;
+    // application processors load cr3 while still in 32bit mode, see smp.rs
+    const PML4_MAX_ADDR: usize = 0xffff_ffff;
+
+    pub fn new() -> Self {
+        let pml4: &'static mut [u8] = match PageBox::new_below(Self::PML4_MAX_ADDR, 1) {
+            Ok(page_box) => page_box.leak(),
+            Err(error) => panic!("failed to alloc the PML4 below 4GiB: {}", error),
+        };
+        pml4.fill(0);
+
+        Self {
+            pml4: pml4.as_mut_ptr() as *mut u64,
+            reserve: &mut [],
+        }
+    }
+
+    // must be called while boot services are still available
+    pub fn reserve(&mut self, pages: usize) {
+        self.reserve = PageBox::new(pages).leak();
+    }
+
+    pub fn map(
+        &mut self,
+        virtual_addr: usize,
+        physical_addr: usize,
+        flags: u64,
+    ) -> Result<(), &'static str> {
+        assert_eq!(virtual_addr % Self::PAGE_SIZE, 0);
+        assert_eq!(physical_addr % Self::PAGE_SIZE, 0);
+
+        let table = self.walk(virtual_addr, 1)?;
+        let entry = unsafe { &mut *table.add(Self::index(virtual_addr, 0)) };
+        *entry = Self::merge_entry(*entry, physical_addr, flags)?;
+        Ok(())
+    }
+
+    pub fn map_huge(
+        &mut self,
+        virtual_addr: usize,
+        physical_addr: usize,
+        flags: u64,
+    ) -> Result<(), &'static str> {
+        assert_eq!(virtual_addr % Self::HUGE_PAGE_SIZE, 0);
+        assert_eq!(physical_addr % Self::HUGE_PAGE_SIZE, 0);
+
+        let table = self.walk(virtual_addr, 2)?;
+        let entry = unsafe { &mut *table.add(Self::index(virtual_addr, 1)) };
+        assert_eq!(
+            *entry & Self::PRESENT,
+            0,
+            "huge page overlaps an existing mapping"
+        );
+        *entry = physical_addr as u64 | flags | Self::HUGE_PAGE | Self::PRESENT;
+        Ok(())
+    }
+
+    pub fn map_range(
+        &mut self,
+        virtual_addr: usize,
+        physical_addr: usize,
+        size: usize,
+        flags: u64,
+    ) -> Result<(), &'static str> {
+        let pages = (size + Self::PAGE_SIZE - 1) / Self::PAGE_SIZE;
+        for i in 0..pages {
+            self.map(
+                virtual_addr + i * Self::PAGE_SIZE,
+                physical_addr + i * Self::PAGE_SIZE,
+                flags,
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn map_huge_range(
+        &mut self,
+        virtual_addr: usize,
+        physical_addr: usize,
+        size: usize,
+        flags: u64,
+    ) -> Result<(), &'static str> {
+        let pages = (size + Self::HUGE_PAGE_SIZE - 1) / Self::HUGE_PAGE_SIZE;
+        for i in 0..pages {
+            self.map_huge(
+                virtual_addr + i * Self::HUGE_PAGE_SIZE,
+                physical_addr + i * Self::HUGE_PAGE_SIZE,
+                flags,
+            )?;
+        }
+        Ok(())
+    }
+
+    // identity-maps [0, size) as non-executable data, except the pages of `exec_ranges`
+    pub fn identity_map(
+        &mut self,
+        size: usize,
+        exec_ranges: &[(usize, usize)],
+    ) -> Result<(), &'static str> {
+        let is_exec = |page: usize| {
+            exec_ranges
+                .iter()
+                .any(|&(start, end)| start & !(Self::PAGE_SIZE - 1) <= page && page < end)
+        };
+        let data_flags = Self::WRITABLE | Self::NO_EXECUTE;
+
+        let mut addr = 0;
+        while addr < size {
+            let huge_end = addr + Self::HUGE_PAGE_SIZE;
+            let has_exec = exec_ranges
+                .iter()
+                .any(|&(start, end)| start < huge_end && addr < end);
+            if !has_exec {
+                self.map_huge(addr, addr, data_flags)?;
+            } else {
+                for page in (addr..huge_end).step_by(Self::PAGE_SIZE) {
+                    if is_exec(page) {
+                        self.map(page, page, 0)?;
+                    } else {
+                        self.map(page, page, data_flags)?;
+                    }
+                }
+            }
+            addr = huge_end;
+        }
+        Ok(())
+    }
+
+    pub fn cr3(&self) -> usize {
+        self.pml4.addr()
+    }
+
+    pub fn enable_protection() {
+        const IA32_EFER: u32 = 0xc000_0080;
+        const EFER_NXE: u64 = 1 << 11;
+        const CR0_WP: u64 = 1 << 16;
+
+        unsafe {
+            let low: u32;
+            let high: u32;
+            asm!("rdmsr", in("ecx") IA32_EFER, out("eax") low, out("edx") high);
+            let efer = ((high as u64) << 32 | low as u64) | EFER_NXE;
+            asm!("wrmsr", in("ecx") IA32_EFER, in("eax") efer as u32, in("edx") (efer >> 32) as u32);
+
+            let cr0: u64;
+            asm!("mov {}, cr0", out(reg) cr0);
+            asm!("mov cr0, {}", in(reg) cr0 | CR0_WP);
+        }
+    }
+
+    fn walk(&mut self, virtual_addr: usize, leaf_level: usize) -> Result<*mut u64, &'static str> {
+        let mut table = self.pml4;
+        for level in (leaf_level..4).rev() {
+            let entry = unsafe { &mut *table.add(Self::index(virtual_addr, level)) };
+            if *entry & Self::PRESENT == 0 {
+                *entry = self.alloc_table()? as u64 | Self::PRESENT | Self::WRITABLE;
+            }
+            assert_eq!(*entry & Self::HUGE_PAGE, 0, "page overlaps a huge page");
+            table = (*entry & Self::ADDR_MASK) as usize as *mut u64;
+        }
+        Ok(table)
+    }
+
+    // Pages shared by two segments get the union of their permissions, unless that union
+    // would be writable and executable while neither segment asked for it.
+    fn merge_entry(entry: u64, physical_addr: usize, flags: u64) -> Result<u64, &'static str> {
+        let new_entry = physical_addr as u64 | flags | Self::PRESENT;
+        if entry & Self::PRESENT == 0 || entry == new_entry {
+            return Ok(new_entry);
+        }
+        if entry & Self::ADDR_MASK != physical_addr as u64 {
+            return Err("page is already mapped to another frame");
+        }
+
+        let no_execute = entry & new_entry & Self::NO_EXECUTE;
+        let merged = (entry | new_entry) & !Self::NO_EXECUTE | no_execute;
+        if Self::is_writable_and_executable(merged)
+            && !Self::is_writable_and_executable(entry)
+            && !Self::is_writable_and_executable(new_entry)
+        {
+            return Err("segments share a page with conflicting permissions");
+        }
+        Ok(merged)
+    }
+
+    fn is_writable_and_executable(entry: u64) -> bool {
+        entry & Self::WRITABLE != 0 && entry & Self::NO_EXECUTE == 0
+    }
+
+    fn index(virtual_addr: usize, level: usize) -> usize {
+        (virtual_addr >> (12 + 9 * level)) & (Self::ENTRIES - 1)
+    }
+
+    // fails once the reserve is used up after exit_boot_services, there is nothing to panic to
+    fn alloc_table(&mut self) -> Result<*mut u64, &'static str> {
+        let table = if self.reserve.is_empty() {
+            let page = efi_wrapper::alloc_pages(1).map_err(|_| "out of pages for page tables")?;
+            unsafe { slice::from_raw_parts_mut(page, Self::PAGE_SIZE) }
+        } else {
+            let reserve = mem::take(&mut self.reserve);
+            let (table, rest) = reserve.split_at_mut(Self::PAGE_SIZE);
+            self.reserve = rest;
+            table
+        };
+        table.fill(0);
+        Ok(table.as_mut_ptr() as *mut u64)
+    }
+}
@@ -0,0 +1,63 @@
+use crate::efi_wrapper;
+use core::arch::asm;
+use core::arch::x86_64::__cpuid;
+use core::arch::x86_64::__cpuid_count;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RandomSource {
+    EfiRng,
+    Rdseed,
+    Rdrand,
+}
+
+// RDRAND/RDSEED can fail transiently when the entropy source is drained
+const RETRIES: usize = 16;
+
+// EFI_RNG_PROTOCOL first, then the CPU instructions; None if nothing is available
+pub fn random_u64() -> Option<(u64, RandomSource)> {
+    let mut buff = [0u8; 8];
+    if efi_wrapper::get_random(&mut buff).is_ok() {
+        return Some((u64::from_le_bytes(buff), RandomSource::EfiRng));
+    }
+    if has_rdseed() {
+        if let Some(value) = retry(rdseed) {
+            return Some((value, RandomSource::Rdseed));
+        }
+    }
+    if has_rdrand() {
+        if let Some(value) = retry(rdrand) {
+            return Some((value, RandomSource::Rdrand));
+        }
+    }
+    None
+}
+
+fn retry(f: fn() -> Option<u64>) -> Option<u64> {
+    (0..RETRIES).find_map(|_| f())
+}
+
+fn has_rdrand() -> bool {
+    __cpuid(1).ecx & (1 << 30) != 0
+}
+
+fn has_rdseed() -> bool {
+    __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 18) != 0
+}
+
+fn rdrand() -> Option<u64> {
+    let value: u64;
+    let ok: u8;
+    unsafe {
+        asm!("rdrand {}", "setc {}", out(reg) value, out(reg_byte) ok);
+    }
+    (ok != 0).then_some(value)
+}
+
+fn rdseed() -> Option<u64> {
+    let value: u64;
+    let ok: u8;
+    unsafe {
+        asm!("rdseed {}", "setc {}", out(reg) value, out(reg_byte) ok);
+    }
+    (ok != 0).then_some(value)
+}
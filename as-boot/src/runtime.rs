@@ -0,0 +1,170 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::LogLevel;
+use crate::efi_wrapper::MemoryMap;
+use crate::error::BootResult;
+use crate::error::Context;
+use crate::paging::PageTable;
+use alloc::vec::Vec;
+use core::mem::size_of;
+use core::ptr;
+use efi::EfiConfigurationTable;
+use efi::EfiMemoryAttributesTable;
+use efi::EfiMemoryDescriptor;
+use efi::EfiMemoryType;
+
+// Runtime regions are mapped at RUNTIME_WINDOW + physical address. The window is PML4 slot 510,
+// just below the kernel, and covers 512GiB of physical address space.
+pub const RUNTIME_WINDOW: usize = 0xffff_ff00_0000_0000;
+const RUNTIME_WINDOW_SIZE: usize = 512 * 1024 * 1024 * 1024;
+
+// tables for the window are built from the final memory map, after exit_boot_services
+pub const PAGE_TABLE_RESERVE: usize = 128;
+
+// EFI_MEMORY_ATTRIBUTES_TABLE, copied while boot services are still available.
+// Runtime code regions hold the data of their images too, the table tells them apart.
+#[derive(Debug)]
+pub struct MemoryAttributes {
+    // (physical start, physical end, attribute)
+    entries: Vec<(usize, usize, u64)>,
+}
+
+impl MemoryAttributes {
+    // None if the firmware does not publish the table
+    pub fn new() -> Option<Self> {
+        let table =
+            efi_wrapper::configuration_table(&EfiConfigurationTable::MEMORY_ATTRIBUTES_TABLE_GUID)?
+                as *const EfiMemoryAttributesTable;
+        let header = unsafe { &*table };
+        let descriptor_size = header.descriptor_size as usize;
+        if descriptor_size < size_of::<EfiMemoryDescriptor>() {
+            log!(
+                LogLevel::Warn,
+                "RUNTIME: memory attributes table has {} byte descriptors, ignoring it",
+                descriptor_size
+            );
+            return None;
+        }
+
+        let first = unsafe { table.add(1) } as *const u8;
+        let entries = (0..header.number_of_entries as usize)
+            .map(|index| {
+                let descriptor = unsafe {
+                    ptr::read_unaligned(
+                        first.add(index * descriptor_size) as *const EfiMemoryDescriptor
+                    )
+                };
+                (
+                    descriptor.physical_start() as usize,
+                    descriptor.physical_end() as usize,
+                    descriptor.attribute(),
+                )
+            })
+            .collect();
+        Some(Self { entries: entries })
+    }
+
+    // page flags for the runtime code page at `physical_addr`
+    fn code_flags(&self, physical_addr: usize) -> u64 {
+        let Some(&(_, _, attribute)) = self
+            .entries
+            .iter()
+            .find(|&&(start, end, _)| start <= physical_addr && physical_addr < end)
+        else {
+            return PageTable::WRITABLE | PageTable::NO_EXECUTE;
+        };
+
+        let mut flags = 0;
+        if attribute & EfiMemoryDescriptor::EFI_MEMORY_RO == 0 {
+            flags |= PageTable::WRITABLE;
+        }
+        if attribute & EfiMemoryDescriptor::EFI_MEMORY_XP != 0 {
+            flags |= PageTable::NO_EXECUTE;
+        }
+        flags
+    }
+}
+
+// Assigns virtual addresses to every EFI_MEMORY_RUNTIME descriptor, maps them in `page_table`
+// and moves the firmware over. Returns the virtual address of the runtime services table.
+pub fn set_virtual_address_map(
+    memory_map: &mut MemoryMap,
+    page_table: &mut PageTable,
+    memory_attributes: Option<&MemoryAttributes>,
+) -> BootResult<Option<usize>> {
+    let runtime_services = efi_wrapper::runtime_services_addr();
+    let mut runtime_services_virtual = None;
+    let mut writable_code = false;
+
+    for index in 0..memory_map.entry_count() {
+        let descriptor = memory_map.descriptor_mut(index);
+        if descriptor.attribute() & EfiMemoryDescriptor::EFI_MEMORY_RUNTIME == 0 {
+            continue;
+        }
+
+        let physical_start = descriptor.physical_start() as usize;
+        let physical_end = descriptor.physical_end() as usize;
+        // boot services are gone, so give up on the virtual map instead of panicking
+        if physical_end > RUNTIME_WINDOW_SIZE {
+            return Err("runtime region is out of the runtime window".into());
+        }
+        let virtual_start = RUNTIME_WINDOW + physical_start;
+        descriptor.set_virtual_start(virtual_start as u64);
+
+        let is_code = descriptor.memory_type() == Some(EfiMemoryType::EfiRuntimeServicesCode);
+        match memory_attributes {
+            Some(memory_attributes) if is_code => {
+                for physical_addr in (physical_start..physical_end).step_by(PageTable::PAGE_SIZE) {
+                    let flags = memory_attributes.code_flags(physical_addr);
+                    writable_code |= flags & (PageTable::WRITABLE | PageTable::NO_EXECUTE)
+                        == PageTable::WRITABLE;
+                    page_table.map(RUNTIME_WINDOW + physical_addr, physical_addr, flags)?;
+                }
+            }
+            _ => {
+                // without the attributes table the data inside code regions has to stay writable
+                let flags = if is_code {
+                    writable_code = true;
+                    PageTable::WRITABLE
+                } else {
+                    PageTable::WRITABLE | PageTable::NO_EXECUTE
+                };
+                page_table.map_range(
+                    virtual_start,
+                    physical_start,
+                    physical_end - physical_start,
+                    flags,
+                )?;
+            }
+        }
+
+        if let Some(addr) = runtime_services {
+            if physical_start <= addr && addr < physical_end {
+                runtime_services_virtual = Some(RUNTIME_WINDOW + addr);
+            }
+        }
+        log!(
+            LogLevel::Debug,
+            "RUNTIME: {:#x}-{:#x} -> {:#x}, type {}",
+            physical_start,
+            physical_end,
+            virtual_start,
+            descriptor.raw_memory_type()
+        );
+    }
+
+    if writable_code {
+        log!(
+            LogLevel::Warn,
+            "RUNTIME: runtime code is mapped writable and executable{}",
+            if memory_attributes.is_some() {
+                ""
+            } else {
+                ", the firmware has no memory attributes table"
+            }
+        );
+    }
+
+    unsafe { efi_wrapper::set_virtual_address_map(memory_map) }
+        .context("SetVirtualAddressMap failed")?;
+    Ok(runtime_services_virtual)
+}
@@ -0,0 +1,82 @@
+use core::arch::asm;
+
+// 16550 UART driven through port I/O, usable before and after exit_boot_services
+#[derive(Clone, Copy, Debug)]
+pub struct SerialPort {
+    base: u16,
+}
+
+impl SerialPort {
+    pub const COM1: u16 = 0x3f8;
+
+    const DATA: u16 = 0;
+    const INTERRUPT_ENABLE: u16 = 1;
+    const FIFO_CONTROL: u16 = 2;
+    const LINE_CONTROL: u16 = 3;
+    const MODEM_CONTROL: u16 = 4;
+    const LINE_STATUS: u16 = 5;
+
+    const LINE_STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+    // 115200 baud, 8N1. Returns None when no UART answers the loopback test.
+    pub fn init(base: u16) -> Option<Self> {
+        let port = Self { base: base };
+
+        unsafe {
+            port.write_register(Self::INTERRUPT_ENABLE, 0x00);
+            port.write_register(Self::LINE_CONTROL, 0x80);
+            port.write_register(Self::DATA, 0x01);
+            port.write_register(Self::INTERRUPT_ENABLE, 0x00);
+            port.write_register(Self::LINE_CONTROL, 0x03);
+            port.write_register(Self::FIFO_CONTROL, 0xc7);
+
+            port.write_register(Self::MODEM_CONTROL, 0x1e);
+            port.write_register(Self::DATA, 0xae);
+            if port.read_register(Self::DATA) != 0xae {
+                return None;
+            }
+            port.write_register(Self::MODEM_CONTROL, 0x0f);
+        }
+
+        Some(port)
+    }
+
+    pub fn write_byte(&self, byte: u8) {
+        unsafe {
+            while self.read_register(Self::LINE_STATUS) & Self::LINE_STATUS_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            self.write_register(Self::DATA, byte);
+        }
+    }
+
+    pub fn write(&self, s: &str) {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+    }
+
+    unsafe fn write_register(&self, register: u16, value: u8) {
+        unsafe {
+            asm!(
+                "out dx, al",
+                in("dx") self.base + register,
+                in("al") value,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+    }
+
+    unsafe fn read_register(&self, register: u16) -> u8 {
+        let value: u8;
+        unsafe {
+            asm!(
+                "in al, dx",
+                in("dx") self.base + register,
+                out("al") value,
+                options(nomem, nostack, preserves_flags)
+            );
+        }
+        value
+    }
+}
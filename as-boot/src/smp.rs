@@ -0,0 +1,330 @@
+use crate::efi_wrapper;
+use crate::efi_wrapper::PageBox;
+use core::arch::asm;
+use core::arch::global_asm;
+use core::mem::offset_of;
+use core::ptr;
+use core::slice;
+use efi::EfiResult;
+
+// Application processor bring-up. The APs are started with INIT-SIPI-SIPI after
+// exit_boot_services, because the firmware takes them back into its own loop when boot
+// services end. Each AP runs `ap_trampoline` from a page below 1MiB, switches to long mode
+// with the loader's page table and GDT and jumps to `entry` with `argument` in rdi.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lapic {
+    // MMIO base address
+    XApic(usize),
+    X2Apic,
+}
+
+impl Lapic {
+    const IA32_APIC_BASE: u32 = 0x1b;
+    const APIC_BASE_X2APIC: u64 = 1 << 10;
+    const APIC_BASE_ADDR_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+    const XAPIC_ID: usize = 0x20;
+    const XAPIC_ICR_LOW: usize = 0x300;
+    const XAPIC_ICR_HIGH: usize = 0x310;
+    const X2APIC_ID: u32 = 0x802;
+    const X2APIC_ICR: u32 = 0x830;
+
+    const ICR_DELIVERY_PENDING: u32 = 1 << 12;
+    const ICR_INIT: u32 = 0x4500;
+    const ICR_STARTUP: u32 = 0x4600;
+
+    // the mode the firmware left the local APIC of the running CPU in
+    pub fn current() -> Self {
+        let apic_base = unsafe { rdmsr(Self::IA32_APIC_BASE) };
+        if apic_base & Self::APIC_BASE_X2APIC != 0 {
+            Self::X2Apic
+        } else {
+            Self::XApic((apic_base & Self::APIC_BASE_ADDR_MASK) as usize)
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        match *self {
+            Self::XApic(base) => unsafe {
+                ptr::read_volatile((base + Self::XAPIC_ID) as *const u32) >> 24
+            },
+            Self::X2Apic => unsafe { rdmsr(Self::X2APIC_ID) as u32 },
+        }
+    }
+
+    fn send_ipi(&self, lapic_id: u32, command: u32) {
+        match *self {
+            Self::XApic(base) => unsafe {
+                let icr_low = (base + Self::XAPIC_ICR_LOW) as *mut u32;
+                ptr::write_volatile((base + Self::XAPIC_ICR_HIGH) as *mut u32, lapic_id << 24);
+                ptr::write_volatile(icr_low, command);
+                while ptr::read_volatile(icr_low) & Self::ICR_DELIVERY_PENDING != 0 {
+                    core::hint::spin_loop();
+                }
+            },
+            Self::X2Apic => unsafe {
+                wrmsr(Self::X2APIC_ICR, (lapic_id as u64) << 32 | command as u64);
+            },
+        }
+    }
+}
+
+// Limine compatible layout: 16bit, 32bit and 64bit code and data segments
+pub const GDT: [u64; 7] = [
+    0,
+    0x0000_9b00_0000_ffff,
+    0x0000_9300_0000_ffff,
+    0x00cf_9b00_0000_ffff,
+    0x00cf_9300_0000_ffff,
+    0x00af_9b00_0000_ffff,
+    0x00cf_9300_0000_ffff,
+];
+pub const CODE32_SELECTOR: u16 = 0x18;
+pub const DATA32_SELECTOR: u16 = 0x20;
+pub const CODE64_SELECTOR: u16 = 0x28;
+pub const DATA64_SELECTOR: u16 = 0x30;
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Gdtr {
+    pub limit: u16,
+    pub base: u64,
+}
+
+impl Gdtr {
+    pub fn new(gdt: &[u64]) -> Self {
+        Self {
+            limit: (size_of_val(gdt) - 1) as u16,
+            base: gdt.as_ptr().addr() as u64,
+        }
+    }
+}
+
+// second page of the trampoline, written by the BSP before each AP is started
+#[repr(C)]
+#[derive(Debug)]
+struct ApTrampolineData {
+    gdt: [u64; 7],
+    gdtr: Gdtr,
+    cr3: u64,
+    // address of the GDTR loaded once in long mode
+    final_gdtr: u64,
+    stack_top: u64,
+    entry: u64,
+    argument: u64,
+    started: u32,
+}
+
+#[derive(Debug)]
+pub struct ApStartup {
+    code: usize,
+    data: *mut ApTrampolineData,
+}
+
+impl ApStartup {
+    // the SIPI vector is the page number of the trampoline
+    const MAX_ADDR: usize = 0xf_ffff;
+    const DATA_OFFSET: usize = PageBox::PAGE_SIZE;
+
+    const INIT_DELAY_MS: usize = 10;
+    const START_TIMEOUT_MS: usize = 100;
+
+    // must be called while boot services are still available
+    pub fn new() -> EfiResult<Self> {
+        let pages: &'static mut [u8] = PageBox::new_below(Self::MAX_ADDR, 2)?.leak();
+        let code = pages.as_ptr().addr();
+
+        let trampoline = ap_trampoline as *const () as usize;
+        let trampoline_len = ap_trampoline_end as *const () as usize - trampoline;
+        assert!(trampoline_len <= Self::DATA_OFFSET);
+        let trampoline_code =
+            unsafe { slice::from_raw_parts(trampoline as *const u8, trampoline_len) };
+        pages[..trampoline_len].copy_from_slice(trampoline_code);
+
+        // far jump targets are absolute, so they are patched for the page the code landed in
+        let patch = |pages: &mut [u8], field: usize, target: usize| {
+            let field = field - trampoline;
+            let target = (code + target - trampoline) as u32;
+            pages[field..field + 4].copy_from_slice(&target.to_le_bytes());
+        };
+        patch(
+            pages,
+            ap_trampoline_far32 as *const () as usize,
+            ap_trampoline_protected as *const () as usize,
+        );
+        patch(
+            pages,
+            ap_trampoline_far64 as *const () as usize,
+            ap_trampoline_long as *const () as usize,
+        );
+
+        let data = pages[Self::DATA_OFFSET..].as_mut_ptr() as *mut ApTrampolineData;
+        unsafe {
+            (&raw mut (*data).gdt).write(GDT);
+            (&raw mut (*data).gdtr).write(Gdtr::new(&(*data).gdt));
+        }
+
+        Ok(Self {
+            code: code,
+            data: data,
+        })
+    }
+
+    // the code page has to stay executable in the page table the APs switch to
+    pub fn exec_range(&self) -> (usize, usize) {
+        (self.code, self.code + Self::DATA_OFFSET)
+    }
+
+    // Starts the AP `lapic_id` and returns whether it reached long mode. The AP runs
+    // `entry(argument)` on `stack_top` with `cr3` (below 4GiB) and the GDT of `gdtr` loaded.
+    pub unsafe fn start(
+        &mut self,
+        lapic: Lapic,
+        lapic_id: u32,
+        cr3: usize,
+        gdtr: &Gdtr,
+        stack_top: usize,
+        entry: usize,
+        argument: usize,
+    ) -> bool {
+        unsafe {
+            let data = &mut *self.data;
+            data.cr3 = cr3 as u64;
+            data.final_gdtr = (gdtr as *const Gdtr).addr() as u64;
+            data.stack_top = stack_top as u64;
+            data.entry = entry as u64;
+            data.argument = argument as u64;
+            ptr::write_volatile(&raw mut data.started, 0);
+        }
+
+        let vector = (self.code / PageBox::PAGE_SIZE) as u32;
+        lapic.send_ipi(lapic_id, Lapic::ICR_INIT);
+        efi_wrapper::delay_ms(Self::INIT_DELAY_MS);
+        lapic.send_ipi(lapic_id, Lapic::ICR_STARTUP | vector);
+        if self.wait_started(1) {
+            return true;
+        }
+        lapic.send_ipi(lapic_id, Lapic::ICR_STARTUP | vector);
+        self.wait_started(Self::START_TIMEOUT_MS)
+    }
+
+    fn wait_started(&self, timeout_ms: usize) -> bool {
+        for _ in 0..=timeout_ms {
+            if unsafe { ptr::read_volatile(&raw const (*self.data).started) } != 0 {
+                return true;
+            }
+            efi_wrapper::delay_ms(1);
+        }
+        false
+    }
+}
+
+unsafe fn rdmsr(msr: u32) -> u64 {
+    let low: u32;
+    let high: u32;
+    unsafe {
+        asm!("rdmsr", in("ecx") msr, out("eax") low, out("edx") high);
+    }
+    (high as u64) << 32 | low as u64
+}
+
+unsafe fn wrmsr(msr: u32, value: u64) {
+    unsafe {
+        asm!("wrmsr", in("ecx") msr, in("eax") value as u32, in("edx") (value >> 32) as u32);
+    }
+}
+
+// Copied to a page below 1MiB, entered in real mode at offset 0 with cs = page >> 4.
+// ebx keeps the linear address of the page all the way to long mode.
+global_asm!(
+    ".global ap_trampoline",
+    ".global ap_trampoline_far32",
+    ".global ap_trampoline_protected",
+    ".global ap_trampoline_far64",
+    ".global ap_trampoline_long",
+    ".global ap_trampoline_end",
+    ".code16",
+    "ap_trampoline:",
+    "cli",
+    "cld",
+    "mov ax, cs",
+    "mov ds, ax",
+    "movzx ebx, ax",
+    "shl ebx, 4",
+    "lgdt [{data_gdtr}]",
+    "mov eax, cr0",
+    "or eax, {cr0_pe}",
+    "mov cr0, eax",
+    // jmp far CODE32_SELECTOR:ap_trampoline_protected
+    ".byte 0x66, 0xea",
+    "ap_trampoline_far32:",
+    ".long 0",
+    ".word {code32}",
+    ".code32",
+    "ap_trampoline_protected:",
+    "mov ax, {data32}",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov eax, cr4",
+    "or eax, {cr4_pae}",
+    "mov cr4, eax",
+    "mov eax, [ebx + {data_cr3}]",
+    "mov cr3, eax",
+    "mov ecx, {ia32_efer}",
+    "rdmsr",
+    "or eax, {efer_lme_nxe}",
+    "wrmsr",
+    "mov eax, cr0",
+    "or eax, {cr0_pg_wp}",
+    "mov cr0, eax",
+    // jmp far CODE64_SELECTOR:ap_trampoline_long
+    ".byte 0xea",
+    "ap_trampoline_far64:",
+    ".long 0",
+    ".word {code64}",
+    ".code64",
+    "ap_trampoline_long:",
+    "mov ax, {data64}",
+    "mov ds, ax",
+    "mov es, ax",
+    "mov ss, ax",
+    "mov fs, ax",
+    "mov gs, ax",
+    "mov ebx, ebx",
+    "mov rax, [rbx + {data_final_gdtr}]",
+    "lgdt [rax]",
+    "mov rsp, [rbx + {data_stack_top}]",
+    "mov rdi, [rbx + {data_argument}]",
+    "mov rax, [rbx + {data_entry}]",
+    // the BSP may reuse the data page for the next AP from here on
+    "mov dword ptr [rbx + {data_started}], 1",
+    "jmp rax",
+    "ap_trampoline_end:",
+    data_gdtr = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, gdtr),
+    data_cr3 = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, cr3),
+    data_final_gdtr = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, final_gdtr),
+    data_stack_top = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, stack_top),
+    data_entry = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, entry),
+    data_argument = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, argument),
+    data_started = const ApStartup::DATA_OFFSET + offset_of!(ApTrampolineData, started),
+    cr0_pe = const 1u32 << 0,
+    cr4_pae = const 1u32 << 5,
+    ia32_efer = const 0xc000_0080u32,
+    efer_lme_nxe = const (1u32 << 8) | (1 << 11),
+    cr0_pg_wp = const (1u32 << 31) | (1 << 16),
+    code32 = const CODE32_SELECTOR,
+    data32 = const DATA32_SELECTOR,
+    code64 = const CODE64_SELECTOR,
+    data64 = const DATA64_SELECTOR,
+);
+
+unsafe extern "C" {
+    fn ap_trampoline();
+    fn ap_trampoline_far32();
+    fn ap_trampoline_protected();
+    fn ap_trampoline_far64();
+    fn ap_trampoline_long();
+    fn ap_trampoline_end();
+}
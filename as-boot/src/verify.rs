@@ -0,0 +1,123 @@
+use crate::config::Entry;
+use crate::efi_wrapper::File;
+use crate::efi_wrapper::LogLevel;
+use crate::error::BootResult;
+use crate::error::Context;
+use bootcrypto::Sha256;
+use bootcrypto::ed25519;
+use core::fmt;
+use core::str;
+
+// Hex Ed25519 public key, set by as-build (`sign`) when as-boot is compiled.
+// With a key embedded, every kernel, module and chainloaded EFI application needs a valid
+// detached signature of its SHA-256 digest in `<path>.sig`; without one only `sha256`
+// in boot.cfg is checked.
+const PUBLIC_KEY: Option<&str> = option_env!("AS_BOOT_PUBLIC_KEY");
+
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+pub fn verify_kernel(entry: &Entry, image: &[u8]) -> BootResult<()> {
+    let digest = bootcrypto::sha256(image);
+    log!(
+        LogLevel::Info,
+        "VERIFY: {} sha256 {}",
+        entry.kernel,
+        Hex(&digest)
+    );
+
+    if let Some(expected) = &entry.sha256 {
+        if *expected != digest {
+            println!(
+                "VERIFY: {} does not match the sha256 in boot.cfg",
+                entry.kernel
+            );
+            println!("VERIFY:   expected {}", Hex(expected));
+            println!("VERIFY:   actual   {}", Hex(&digest));
+            return Err("kernel image does not match its sha256, refusing to boot".into());
+        }
+        log!(LogLevel::Info, "VERIFY: sha256 matches boot.cfg");
+    }
+
+    verify_signature(&entry.kernel, &digest)
+}
+
+// boot.cfg is not signed, so with a key embedded `efi =` and `module =` must not be a way
+// around it
+pub fn verify_file(path: &str, image: &[u8]) -> BootResult<()> {
+    if PUBLIC_KEY.is_none() {
+        return Ok(());
+    }
+
+    let digest = bootcrypto::sha256(image);
+    log!(LogLevel::Info, "VERIFY: {} sha256 {}", path, Hex(&digest));
+    verify_signature(path, &digest)
+}
+
+fn verify_signature(path: &str, digest: &[u8; Sha256::DIGEST_SIZE]) -> BootResult<()> {
+    let Some(public_key) = PUBLIC_KEY else {
+        return Ok(());
+    };
+
+    let public_key: [u8; ed25519::PUBLIC_KEY_SIZE] =
+        parse_hex(public_key).ok_or("embedded public key is invalid")?;
+    let signature = load_signature(path)?;
+    if !ed25519::verify(&public_key, digest, &signature) {
+        println!(
+            "VERIFY: signature of {} does not match the embedded key {}",
+            path,
+            Hex(&public_key)
+        );
+        return Err("signature is invalid, refusing to boot".into());
+    }
+    log!(LogLevel::Info, "VERIFY: signature is valid");
+
+    Ok(())
+}
+
+fn load_signature(image: &str) -> BootResult<[u8; ed25519::SIGNATURE_SIZE]> {
+    let path = format!("{}{}", image, SIGNATURE_SUFFIX);
+    let (page_box, size) = match File::load(&path) {
+        Ok(loaded) => loaded,
+        Err(error) => {
+            println!("VERIFY: failed to load {}: {}", path, error);
+            return Err(error).context("signature is missing, refusing to boot");
+        }
+    };
+
+    let mut signature = [0u8; ed25519::SIGNATURE_SIZE];
+    if size != signature.len() {
+        return Err("signature is malformed, refusing to boot".into());
+    }
+    signature.copy_from_slice(&page_box[..size]);
+    Ok(signature)
+}
+
+// exactly N bytes as 2N hex digits
+pub fn parse_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2 {
+        return None;
+    }
+
+    // from_str_radix alone would also take a sign, "+a" is not a byte
+    if !s.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut bytes = [0u8; N];
+    for (byte, digits) in bytes.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+        let digits = str::from_utf8(digits).ok()?;
+        *byte = u8::from_str_radix(digits, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
@@ -1,23 +1,75 @@
+use bootcrypto::ed25519;
 use fatfs::{FatType, FileSystem, FormatVolumeOptions, FsOptions};
+use flate2::{Compression, write::GzEncoder};
 use std::{
     env,
-    fs::{File, OpenOptions},
-    io::{Read, Write},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Read, Write},
     path::Path,
-    process::Command,
+    process::{Command, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
 };
 
+// printed by as-boot on COM1 right before it jumps to the kernel
+const BOOT_OK_MARKER: &str = "BOOT: jumping to kernel entry";
+// printed by as-kernel on COM1 once it checked the boot info, and by limine-test
+// once it checked every Limine response
+const KERNEL_OK_MARKER: &str = "KERNEL: booted";
+const TEST_TIMEOUT: Duration = Duration::from_secs(60);
+// with KASLR the kernel entry printed in the marker line has to change between boots
+const KASLR_TEST_BOOTS: usize = 3;
+
+// as-kernel/.cargo/config.toml links a static executable at a fixed address.
+// `kaslr` replaces those flags to build a static PIE that as-boot relocates.
+const KERNEL_PIE_RUSTFLAGS: &str = "-Cforce-unwind-tables=yes -Cforce-frame-pointers=yes \
+    -Cno-redzone -Crelocation-model=pie -Clink-arg=-pie -Clink-arg=--no-dynamic-linker";
+
+// `limine` puts limine-test on the disk instead of as-kernel, a minimal kernel that
+// boots through the Limine protocol and prints every response it got
+const AS_KERNEL: &str = "as-kernel";
+const LIMINE_TEST_KERNEL: &str = "limine-test";
+
+// Ed25519 seed for `sign`, generated on first use. as-boot is built with the matching
+// public key embedded and then only boots kernels with a valid kernel.elf.sig
+// (and only chainloads EFI applications that have a `.sig` next to them).
+const SIGNING_KEY_PATH: &str = "target/kernel-signing.key";
+
+// OVMF.fd holds the variable store too, so QEMU gets a writable copy as pflash
+// and NVRAM variables persist between runs
+const OVMF_PATH: &str = "ovmf/OVMF.fd";
+const OVMF_FLASH_PATH: &str = "target/OVMF.fd";
+
 fn main() {
     let args: Vec<_> = env::args().collect();
-    let release_flag = 2 <= args.len() && args[1] == "release";
+    let release_flag = args[1..].iter().any(|arg| arg == "release");
+    let test_flag = args[1..].iter().any(|arg| arg == "test");
+    let kaslr_flag = args[1..].iter().any(|arg| arg == "kaslr");
+    let sign_flag = args[1..].iter().any(|arg| arg == "sign");
+    let compress_flag = args[1..].iter().any(|arg| arg == "compress");
+    let limine_flag = args[1..].iter().any(|arg| arg == "limine");
 
-    build_as_boot(release_flag);
-    build_as_kernel(release_flag);
-    create_disk(release_flag);
-    run_qemu();
+    let kernel = if limine_flag {
+        LIMINE_TEST_KERNEL
+    } else {
+        AS_KERNEL
+    };
+    let signing_key = sign_flag.then(load_signing_key);
+    build_as_boot(release_flag, signing_key.as_ref());
+    build_kernel(kernel, release_flag, kaslr_flag);
+    create_disk(kernel, release_flag, compress_flag, signing_key.as_ref());
+    prepare_flash();
+    if test_flag && kaslr_flag {
+        test_kaslr();
+    } else if test_flag {
+        test_qemu();
+    } else {
+        run_qemu();
+    }
 }
 
-fn build_as_boot(release_flag: bool) {
+fn build_as_boot(release_flag: bool, signing_key: Option<&[u8; ed25519::SECRET_KEY_SIZE]>) {
     println!("building as-boot ...");
     env::set_current_dir("as-boot").unwrap();
     let mut command = Command::new("cargo");
@@ -25,27 +77,77 @@ fn build_as_boot(release_flag: bool) {
     if release_flag {
         command.arg("--release");
     }
+    match signing_key {
+        Some(signing_key) => {
+            command.env("AS_BOOT_PUBLIC_KEY", hex(&ed25519::public_key(signing_key)));
+        }
+        None => {
+            command.env_remove("AS_BOOT_PUBLIC_KEY");
+        }
+    }
     if !command.status().unwrap().success() {
         panic!("failed to build as-boot");
     }
     env::set_current_dir("../").unwrap();
 }
 
-fn build_as_kernel(release_flag: bool) {
-    println!("building as-kernel ...");
-    env::set_current_dir("as-kernel").unwrap();
+fn build_kernel(kernel: &str, release_flag: bool, kaslr_flag: bool) {
+    println!("building {} ...", kernel);
+    env::set_current_dir(kernel).unwrap();
     let mut command = Command::new("cargo");
     command.arg("build");
     if release_flag {
         command.arg("--release");
     }
+    if kaslr_flag {
+        command.env(
+            "RUSTFLAGS",
+            format!(
+                "{} -Clink-arg=-T./{}/linker.ld",
+                KERNEL_PIE_RUSTFLAGS, kernel
+            ),
+        );
+    }
     if !command.status().unwrap().success() {
-        panic!("failed to build as-boot");
+        panic!("failed to build {}", kernel);
     }
     env::set_current_dir("../").unwrap();
 }
 
-fn create_disk(release_flag: bool) {
+fn load_signing_key() -> [u8; ed25519::SECRET_KEY_SIZE] {
+    let mut signing_key = [0u8; ed25519::SECRET_KEY_SIZE];
+    if Path::new(SIGNING_KEY_PATH).exists() {
+        let bytes = fs::read(SIGNING_KEY_PATH).unwrap();
+        if bytes.len() != signing_key.len() {
+            panic!(
+                "{} is not a {} byte key",
+                SIGNING_KEY_PATH,
+                signing_key.len()
+            );
+        }
+        signing_key.copy_from_slice(&bytes);
+    } else {
+        println!("generating {} ...", SIGNING_KEY_PATH);
+        File::open("/dev/urandom")
+            .unwrap()
+            .read_exact(&mut signing_key)
+            .unwrap();
+        fs::create_dir_all("target").unwrap();
+        fs::write(SIGNING_KEY_PATH, signing_key).unwrap();
+    }
+    signing_key
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn create_disk(
+    kernel: &str,
+    release_flag: bool,
+    compress_flag: bool,
+    signing_key: Option<&[u8; ed25519::SECRET_KEY_SIZE]>,
+) {
     println!("creating disk image ...");
 
     let as_boot_path = if release_flag {
@@ -58,13 +160,25 @@ fn create_disk(release_flag: bool) {
     as_boot.read_to_end(&mut as_boot_vec).unwrap();
 
     let as_kernel_path = if release_flag {
-        Path::new("target/x86_64-unknown-none/release/as-kernel")
+        format!("target/x86_64-unknown-none/release/{}", kernel)
     } else {
-        Path::new("target/x86_64-unknown-none/debug/as-kernel")
+        format!("target/x86_64-unknown-none/debug/{}", kernel)
     };
     let mut as_kernel = File::open(as_kernel_path).unwrap();
     let mut as_kernel_vec = Vec::new();
     as_kernel.read_to_end(&mut as_kernel_vec).unwrap();
+    // as-boot recognizes the gzip magic, the file keeps its name
+    if compress_flag {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&as_kernel_vec).unwrap();
+        let compressed = encoder.finish().unwrap();
+        println!(
+            "compressed kernel.elf from {} to {} bytes",
+            as_kernel_vec.len(),
+            compressed.len()
+        );
+        as_kernel_vec = compressed;
+    }
 
     let disk_path = Path::new("target/disk.img");
     let mut disk = OpenOptions::new()
@@ -93,21 +207,151 @@ fn create_disk(release_flag: bool) {
 
     let mut kernel = file_system_root.create_file("kernel.elf").unwrap();
     kernel.write_all(&as_kernel_vec).unwrap();
+
+    // as-boot checks the stored file against `sha256 =` in boot.cfg and kernel.elf.sig
+    let kernel_digest = bootcrypto::sha256(&as_kernel_vec);
+    println!("kernel.elf sha256 {}", hex(&kernel_digest));
+    if let Some(signing_key) = signing_key {
+        let signature = ed25519::sign(signing_key, &kernel_digest);
+        let mut kernel_sig = file_system_root.create_file("kernel.elf.sig").unwrap();
+        kernel_sig.write_all(&signature).unwrap();
+        println!(
+            "kernel.elf.sig signed with {}",
+            hex(&ed25519::public_key(signing_key))
+        );
+    }
+
+    let boot_cfg_path = Path::new("boot.cfg");
+    if boot_cfg_path.exists() {
+        let mut boot_cfg_vec = Vec::new();
+        File::open(boot_cfg_path)
+            .unwrap()
+            .read_to_end(&mut boot_cfg_vec)
+            .unwrap();
+        let mut boot_cfg = file_system_root.create_file("boot.cfg").unwrap();
+        boot_cfg.write_all(&boot_cfg_vec).unwrap();
+    }
+}
+
+fn prepare_flash() {
+    if !Path::new(OVMF_FLASH_PATH).exists() {
+        fs::copy(OVMF_PATH, OVMF_FLASH_PATH).unwrap();
+    }
+}
+
+fn qemu_command() -> Command {
+    let mut command = Command::new("qemu-system-x86_64");
+    command.args([
+        "-drive",
+        &format!("if=pflash,format=raw,file={}", OVMF_FLASH_PATH),
+        "-drive",
+        "format=raw,file=target/disk.img",
+        "-m",
+        "512M",
+        "-serial",
+        "stdio",
+        // backs EFI_RNG_PROTOCOL in OVMF, as-boot's source of randomness for KASLR
+        "-device",
+        "virtio-rng-pci",
+        // several processors so the CPU topology in the boot log is worth reading
+        "-smp",
+        "4",
+    ]);
+    command
 }
 
 fn run_qemu() {
     println!("running qemu ...");
 
-    Command::new("qemu-system-x86_64")
-        .args([
-            "-bios",
-            "ovmf/OVMF.fd",
-            "-drive",
-            "format=raw,file=target/disk.img",
-            "-m",
-            "512M",
-            "--enable-kvm",
-        ])
-        .status()
+    qemu_command().arg("--enable-kvm").status().unwrap();
+}
+
+// boots headless, echoes the serial log and checks that the kernel got control
+fn test_qemu() {
+    println!("testing on qemu ...");
+
+    match boot_qemu() {
+        Some(_) => println!("test passed"),
+        None => panic!(
+            "test failed: `{}` and `{}` were not found in the boot log",
+            BOOT_OK_MARKER, KERNEL_OK_MARKER
+        ),
+    }
+}
+
+// boots a relocatable kernel several times and checks that it is not always at the same address
+fn test_kaslr() {
+    println!("testing kaslr on qemu ...");
+
+    let mut markers = Vec::new();
+    for boot in 1..=KASLR_TEST_BOOTS {
+        println!("boot {}/{}", boot, KASLR_TEST_BOOTS);
+        match boot_qemu() {
+            Some(marker) => markers.push(marker),
+            None => panic!(
+                "test failed: `{}` and `{}` were not found in the boot log",
+                BOOT_OK_MARKER, KERNEL_OK_MARKER
+            ),
+        }
+    }
+
+    if markers.iter().all(|marker| *marker == markers[0]) {
+        panic!(
+            "test failed: kernel entry did not change in {} boots: `{}`",
+            KASLR_TEST_BOOTS, markers[0]
+        );
+    }
+    println!("test passed");
+}
+
+// returns the as-boot marker line, None if it or the kernel marker after it
+// did not show up within TEST_TIMEOUT
+fn boot_qemu() -> Option<String> {
+    let mut command = qemu_command();
+    command.args(["-display", "none", "-no-reboot"]);
+    if Path::new("/dev/kvm").exists() {
+        command.arg("--enable-kvm");
+    }
+    let mut qemu = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .spawn()
         .unwrap();
+
+    let stdout = qemu.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = Instant::now() + TEST_TIMEOUT;
+    let mut marker = None;
+    let mut kernel_booted = false;
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(timeout) {
+            Ok(line) => {
+                let line = line.trim_end_matches('\r');
+                println!("| {}", line);
+                if line.contains(BOOT_OK_MARKER) {
+                    marker = Some(line.to_string());
+                } else if marker.is_some() && line.contains(KERNEL_OK_MARKER) {
+                    kernel_booted = true;
+                    break;
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    let _ = qemu.kill();
+    let _ = qemu.wait();
+
+    marker.filter(|_| kernel_booted)
 }
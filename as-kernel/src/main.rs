@@ -1,11 +1,36 @@
 #![no_main]
 #![no_std]
 
+mod runtime;
+mod serial;
+
+use bootinfo::BootInfo;
 use core::arch::asm;
 use core::panic::PanicInfo;
+use efi::EfiResetType;
+use runtime::RuntimeServices;
 
 #[unsafe(no_mangle)]
-pub extern "C" fn _start() -> ! {
+pub extern "sysv64" fn _start(boot_info: &'static BootInfo) -> ! {
+    if !boot_info.is_valid() {
+        panic!("invalid boot info");
+    }
+    // as-build test waits for this line
+    serial::write("KERNEL: booted\r\n");
+
+    // `poweroff` and `reboot` on the command line go straight back to the firmware
+    if let Some(runtime_services) = RuntimeServices::new(boot_info) {
+        // as-boot built boot_info and the identity map it points into is still in place
+        let cmdline = unsafe { boot_info.cmdline() };
+        for arg in cmdline.split_whitespace() {
+            match arg {
+                "poweroff" => runtime_services.reset_system(EfiResetType::EfiResetShutdown),
+                "reboot" => runtime_services.reset_system(EfiResetType::EfiResetCold),
+                _ => (),
+            }
+        }
+    }
+
     loop {
         unsafe {
             asm!("hlt");
@@ -0,0 +1,22 @@
+use bootinfo::BootInfo;
+use core::ptr;
+use efi::EFI_STATUS_SUCCESS;
+use efi::EfiResetType;
+use efi::EfiRuntimeServices;
+
+// UEFI runtime services relocated by as-boot into the runtime window of the boot page table
+#[derive(Clone, Copy, Debug)]
+pub struct RuntimeServices {
+    table: &'static EfiRuntimeServices,
+}
+
+impl RuntimeServices {
+    pub fn new(boot_info: &BootInfo) -> Option<Self> {
+        let table = boot_info.runtime_services as usize as *const EfiRuntimeServices;
+        unsafe { table.as_ref() }.map(|table| Self { table: table })
+    }
+
+    pub fn reset_system(&self, reset_type: EfiResetType) -> ! {
+        unsafe { (self.table.reset_system)(reset_type, EFI_STATUS_SUCCESS, 0, ptr::null()) }
+    }
+}
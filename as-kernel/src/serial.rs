@@ -0,0 +1,35 @@
+use core::arch::asm;
+
+// COM1 as left by as-boot (115200 baud, 8N1), so the kernel only has to poll and write
+const COM1: u16 = 0x3f8;
+const LINE_STATUS: u16 = 5;
+const LINE_STATUS_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+pub fn write(s: &str) {
+    for byte in s.bytes() {
+        write_byte(byte);
+    }
+}
+
+fn write_byte(byte: u8) {
+    unsafe {
+        while inb(COM1 + LINE_STATUS) & LINE_STATUS_TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        outb(COM1, byte);
+    }
+}
+
+unsafe fn inb(port: u16) -> u8 {
+    let value: u8;
+    unsafe {
+        asm!("in al, dx", in("dx") port, out("al") value, options(nomem, nostack));
+    }
+    value
+}
+
+unsafe fn outb(port: u16, value: u8) {
+    unsafe {
+        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack));
+    }
+}
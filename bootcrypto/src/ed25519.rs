@@ -0,0 +1,177 @@
+use crate::point::Point;
+use crate::scalar::Scalar;
+use crate::sha512::Sha512;
+
+// RFC 8032 Ed25519 (pure, no context)
+pub const PUBLIC_KEY_SIZE: usize = 32;
+pub const SECRET_KEY_SIZE: usize = 32;
+pub const SIGNATURE_SIZE: usize = 64;
+
+pub fn verify(
+    public_key: &[u8; PUBLIC_KEY_SIZE],
+    message: &[u8],
+    signature: &[u8; SIGNATURE_SIZE],
+) -> bool {
+    let Some(a) = Point::decompress(public_key) else {
+        return false;
+    };
+    let mut r = [0u8; 32];
+    r.copy_from_slice(&signature[..32]);
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&signature[32..]);
+    if !Scalar::is_canonical(&s) {
+        return false;
+    }
+
+    let k = challenge(&r, public_key, message);
+    // [S]B - [k]A has to be R
+    let check = Point::base().mul(&s).add(&a.neg().mul(&k.to_bytes()));
+    check.compress() == r
+}
+
+// `secret_key` is the 32 byte seed
+pub fn public_key(secret_key: &[u8; SECRET_KEY_SIZE]) -> [u8; PUBLIC_KEY_SIZE] {
+    let (a, _) = expand_secret_key(secret_key);
+    Point::base().mul(&a).compress()
+}
+
+pub fn sign(secret_key: &[u8; SECRET_KEY_SIZE], message: &[u8]) -> [u8; SIGNATURE_SIZE] {
+    let (a, prefix) = expand_secret_key(secret_key);
+    let public_key = Point::base().mul(&a).compress();
+
+    let mut hasher = Sha512::new();
+    hasher.update(&prefix);
+    hasher.update(message);
+    let r = Scalar::from_bytes_mod_order(&hasher.finalize());
+    let r_encoded = Point::base().mul(&r.to_bytes()).compress();
+
+    let k = challenge(&r_encoded, &public_key, message);
+    let s = r.add(&k.mul(&Scalar::from_bytes_mod_order(&a)));
+
+    let mut signature = [0u8; SIGNATURE_SIZE];
+    signature[..32].copy_from_slice(&r_encoded);
+    signature[32..].copy_from_slice(&s.to_bytes());
+    signature
+}
+
+// clamped scalar and nonce prefix derived from the seed
+fn expand_secret_key(secret_key: &[u8; SECRET_KEY_SIZE]) -> ([u8; 32], [u8; 32]) {
+    let mut hasher = Sha512::new();
+    hasher.update(secret_key);
+    let hash = hasher.finalize();
+
+    let mut a = [0u8; 32];
+    a.copy_from_slice(&hash[..32]);
+    a[0] &= 248;
+    a[31] &= 127;
+    a[31] |= 64;
+    let mut prefix = [0u8; 32];
+    prefix.copy_from_slice(&hash[32..]);
+    (a, prefix)
+}
+
+// SHA-512(R || A || M) mod L
+fn challenge(r: &[u8; 32], public_key: &[u8; PUBLIC_KEY_SIZE], message: &[u8]) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update(r);
+    hasher.update(public_key);
+    hasher.update(message);
+    Scalar::from_bytes_mod_order(&hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_hex;
+
+    // RFC 8032 7.1 tests 1-3: (secret key, public key, message, signature)
+    const VECTORS: [(&str, &str, &[u8], &str); 3] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            b"",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            b"\x72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            b"\xaf\x82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+             18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        ),
+    ];
+
+    // L, the order of the base point, little endian
+    const ORDER: &str = "edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010";
+
+    #[test]
+    fn rfc8032_vectors() {
+        for (secret_key, public, message, signature) in VECTORS {
+            let secret_key: [u8; SECRET_KEY_SIZE] = from_hex(secret_key);
+            let public: [u8; PUBLIC_KEY_SIZE] = from_hex(public);
+            let signature: [u8; SIGNATURE_SIZE] = from_hex(signature);
+
+            assert_eq!(public_key(&secret_key), public);
+            assert_eq!(sign(&secret_key, message), signature);
+            assert!(verify(&public, message, &signature));
+        }
+    }
+
+    #[test]
+    fn rejects_tampering() {
+        let (_, public, message, signature) = VECTORS[2];
+        let public: [u8; PUBLIC_KEY_SIZE] = from_hex(public);
+        let signature: [u8; SIGNATURE_SIZE] = from_hex(signature);
+
+        let mut bad_signature = signature;
+        bad_signature[0] ^= 1;
+        assert!(!verify(&public, message, &bad_signature));
+        assert!(!verify(&public, b"\xaf\x83", &signature));
+
+        let (_, other_public, _, _) = VECTORS[1];
+        assert!(!verify(&from_hex(other_public), message, &signature));
+    }
+
+    #[test]
+    fn rejects_non_canonical_s() {
+        let (_, public, message, signature) = VECTORS[0];
+        let public: [u8; PUBLIC_KEY_SIZE] = from_hex(public);
+        let mut signature: [u8; SIGNATURE_SIZE] = from_hex(signature);
+
+        // S + L is the same scalar, but only S < L is a valid encoding
+        let order: [u8; 32] = from_hex(ORDER);
+        let mut carry = 0u16;
+        for (byte, order_byte) in signature[32..].iter_mut().zip(order) {
+            let sum = *byte as u16 + order_byte as u16 + carry;
+            *byte = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(carry, 0);
+        assert!(!verify(&public, message, &signature));
+    }
+
+    #[test]
+    fn rejects_bad_public_keys() {
+        let (_, _, message, signature) = VECTORS[0];
+        let signature: [u8; SIGNATURE_SIZE] = from_hex(signature);
+
+        // y = 2 is not the y coordinate of any curve point
+        let mut off_curve = [0u8; PUBLIC_KEY_SIZE];
+        off_curve[0] = 2;
+        assert!(!verify(&off_curve, message, &signature));
+
+        // y = p is not a canonical encoding
+        let mut non_canonical = [0xff; PUBLIC_KEY_SIZE];
+        non_canonical[0] = 0xed;
+        non_canonical[31] = 0x7f;
+        assert!(!verify(&non_canonical, message, &signature));
+    }
+}
@@ -0,0 +1,177 @@
+use core::array;
+
+// Integers mod p = 2^255 - 19 in five 51-bit limbs.
+// add, sub and mul keep every limb below 2^52, so products fit in u128.
+#[derive(Clone, Copy, Debug)]
+pub struct Fe([u64; 5]);
+
+impl Fe {
+    pub const ZERO: Self = Self([0; 5]);
+    pub const ONE: Self = Self([1, 0, 0, 0, 0]);
+
+    const MASK: u64 = (1 << 51) - 1;
+    // 2p, added before subtracting so that limbs never go negative
+    const TWO_P: [u64; 5] = [
+        0xfffffffffffda,
+        0xffffffffffffe,
+        0xffffffffffffe,
+        0xffffffffffffe,
+        0xffffffffffffe,
+    ];
+
+    // exponents as little endian bytes
+    const P_MINUS_2: [u8; 32] = Self::exponent(0xeb, 0x7f);
+    const P_MINUS_5_DIV_8: [u8; 32] = Self::exponent(0xfd, 0x0f);
+
+    const fn exponent(low: u8, high: u8) -> [u8; 32] {
+        let mut bytes = [0xff; 32];
+        bytes[0] = low;
+        bytes[31] = high;
+        bytes
+    }
+
+    pub const fn from_limbs(limbs: [u64; 5]) -> Self {
+        Self(limbs)
+    }
+
+    // the top bit is ignored, values >= p are accepted and reduced
+    pub fn from_bytes(bytes: &[u8; 32]) -> Self {
+        let load = |offset: usize| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(word)
+        };
+
+        Self([
+            load(0) & Self::MASK,
+            (load(6) >> 3) & Self::MASK,
+            (load(12) >> 6) & Self::MASK,
+            (load(19) >> 1) & Self::MASK,
+            (load(24) >> 12) & Self::MASK,
+        ])
+    }
+
+    // canonical encoding, fully reduced mod p
+    pub fn to_bytes(self) -> [u8; 32] {
+        let mut limbs = Self::carry(self.0).0;
+
+        // limbs + 19 overflows 2^255 exactly when the value is >= p
+        let mut q = (limbs[0] + 19) >> 51;
+        for limb in &limbs[1..] {
+            q = (limb + q) >> 51;
+        }
+        limbs[0] += 19 * q;
+        for i in 0..4 {
+            limbs[i + 1] += limbs[i] >> 51;
+            limbs[i] &= Self::MASK;
+        }
+        limbs[4] &= Self::MASK;
+
+        let mut bytes = [0u8; 32];
+        let mut acc: u128 = 0;
+        let mut acc_bits = 0;
+        let mut index = 0;
+        for limb in limbs {
+            acc |= (limb as u128) << acc_bits;
+            acc_bits += 51;
+            while acc_bits >= 8 && index < 32 {
+                bytes[index] = acc as u8;
+                acc >>= 8;
+                acc_bits -= 8;
+                index += 1;
+            }
+        }
+        if index < 32 {
+            bytes[index] = acc as u8;
+        }
+        bytes
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.to_bytes() == [0; 32]
+    }
+
+    // "negative" in the sense of RFC 8032: the encoding is odd
+    pub fn is_negative(&self) -> bool {
+        self.to_bytes()[0] & 1 == 1
+    }
+
+    pub fn equals(&self, rhs: &Self) -> bool {
+        self.to_bytes() == rhs.to_bytes()
+    }
+
+    pub fn add(&self, rhs: &Self) -> Self {
+        Self::carry(array::from_fn(|i| self.0[i] + rhs.0[i]))
+    }
+
+    pub fn sub(&self, rhs: &Self) -> Self {
+        Self::carry(array::from_fn(|i| self.0[i] + Self::TWO_P[i] - rhs.0[i]))
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::ZERO.sub(self)
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let a = self.0.map(|limb| limb as u128);
+        let b = rhs.0.map(|limb| limb as u128);
+        // limbs above 2^255 wrap around multiplied by 19
+        let b19 = b.map(|limb| limb * 19);
+
+        let c = [
+            a[0] * b[0] + a[1] * b19[4] + a[2] * b19[3] + a[3] * b19[2] + a[4] * b19[1],
+            a[0] * b[1] + a[1] * b[0] + a[2] * b19[4] + a[3] * b19[3] + a[4] * b19[2],
+            a[0] * b[2] + a[1] * b[1] + a[2] * b[0] + a[3] * b19[4] + a[4] * b19[3],
+            a[0] * b[3] + a[1] * b[2] + a[2] * b[1] + a[3] * b[0] + a[4] * b19[4],
+            a[0] * b[4] + a[1] * b[3] + a[2] * b[2] + a[3] * b[1] + a[4] * b[0],
+        ];
+
+        let mut limbs = [0u64; 5];
+        let mut carry: u128 = 0;
+        for i in 0..5 {
+            let value = c[i] + carry;
+            limbs[i] = value as u64 & Self::MASK;
+            carry = value >> 51;
+        }
+        let value = limbs[0] as u128 + carry * 19;
+        limbs[0] = value as u64 & Self::MASK;
+        limbs[1] += (value >> 51) as u64;
+        Self(limbs)
+    }
+
+    pub fn square(&self) -> Self {
+        self.mul(self)
+    }
+
+    pub fn invert(&self) -> Self {
+        self.pow(&Self::P_MINUS_2)
+    }
+
+    // self^((p-5)/8), the core of square roots mod p
+    pub fn pow_p58(&self) -> Self {
+        self.pow(&Self::P_MINUS_5_DIV_8)
+    }
+
+    fn pow(&self, exponent: &[u8; 32]) -> Self {
+        let mut result = Self::ONE;
+        for byte in exponent.iter().rev() {
+            for bit in (0..8).rev() {
+                result = result.square();
+                if (byte >> bit) & 1 == 1 {
+                    result = result.mul(self);
+                }
+            }
+        }
+        result
+    }
+
+    fn carry(mut limbs: [u64; 5]) -> Self {
+        for i in 0..4 {
+            limbs[i + 1] += limbs[i] >> 51;
+            limbs[i] &= Self::MASK;
+        }
+        limbs[0] += 19 * (limbs[4] >> 51);
+        limbs[4] &= Self::MASK;
+        Self(limbs)
+    }
+}
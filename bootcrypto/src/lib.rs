@@ -0,0 +1,27 @@
+#![no_std]
+
+// Hashes and signatures used to check the kernel image before as-boot jumps to it.
+// Everything here is variable-time: fine for verifying public data, and for signing
+// in as-build on the developer's machine, but not for handling secrets on shared hosts.
+
+pub mod ed25519;
+mod field;
+mod point;
+mod scalar;
+mod sha256;
+mod sha512;
+
+pub use sha256::Sha256;
+pub use sha256::sha256;
+pub use sha512::Sha512;
+pub use sha512::sha512;
+
+#[cfg(test)]
+fn from_hex<const N: usize>(s: &str) -> [u8; N] {
+    let mut bytes = [0u8; N];
+    assert_eq!(s.len(), N * 2);
+    for (byte, digits) in bytes.iter_mut().zip(s.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(core::str::from_utf8(digits).unwrap(), 16).unwrap();
+    }
+    bytes
+}
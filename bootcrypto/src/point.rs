@@ -0,0 +1,148 @@
+use crate::field::Fe;
+
+// A point of edwards25519 (-x^2 + y^2 = 1 + d x^2 y^2) in extended coordinates:
+// x = X/Z, y = Y/Z, x*y = T/Z.
+#[derive(Clone, Copy, Debug)]
+pub struct Point {
+    x: Fe,
+    y: Fe,
+    z: Fe,
+    t: Fe,
+}
+
+impl Point {
+    pub const IDENTITY: Self = Self {
+        x: Fe::ZERO,
+        y: Fe::ONE,
+        z: Fe::ONE,
+        t: Fe::ZERO,
+    };
+
+    // d = -121665/121666
+    const D: Fe = Fe::from_limbs([
+        0x34dca135978a3,
+        0x1a8283b156ebd,
+        0x5e7a26001c029,
+        0x739c663a03cbb,
+        0x52036cee2b6ff,
+    ]);
+    const D2: Fe = Fe::from_limbs([
+        0x69b9426b2f159,
+        0x35050762add7a,
+        0x3cf44c0038052,
+        0x6738cc7407977,
+        0x2406d9dc56dff,
+    ]);
+    // 2^((p-1)/4)
+    const SQRT_M1: Fe = Fe::from_limbs([
+        0x61b274a0ea0b0,
+        0x0d5a5fc8f189d,
+        0x7ef5e9cbd0c60,
+        0x78595a6804c9e,
+        0x2b8324804fc1d,
+    ]);
+
+    // y = 4/5 with even x
+    const BASE_ENCODED: [u8; 32] = {
+        let mut bytes = [0x66; 32];
+        bytes[0] = 0x58;
+        bytes
+    };
+
+    pub fn base() -> Self {
+        Self::decompress(&Self::BASE_ENCODED).unwrap()
+    }
+
+    // RFC 8032 5.1.3, None for encodings that are not on the curve or not canonical
+    pub fn decompress(bytes: &[u8; 32]) -> Option<Self> {
+        let sign = bytes[31] >> 7 == 1;
+        let mut y_bytes = *bytes;
+        y_bytes[31] &= 0x7f;
+        let y = Fe::from_bytes(&y_bytes);
+        if y.to_bytes() != y_bytes {
+            return None;
+        }
+
+        // x^2 = (y^2 - 1) / (d y^2 + 1)
+        let y2 = y.square();
+        let u = y2.sub(&Fe::ONE);
+        let v = Self::D.mul(&y2).add(&Fe::ONE);
+        let v3 = v.square().mul(&v);
+        let v7 = v3.square().mul(&v);
+        let mut x = u.mul(&v3).mul(&u.mul(&v7).pow_p58());
+
+        let vx2 = v.mul(&x.square());
+        if !vx2.equals(&u) {
+            if !vx2.equals(&u.neg()) {
+                return None;
+            }
+            x = x.mul(&Self::SQRT_M1);
+        }
+
+        if x.is_zero() && sign {
+            return None;
+        }
+        if x.is_negative() != sign {
+            x = x.neg();
+        }
+
+        Some(Self {
+            x: x,
+            y: y,
+            z: Fe::ONE,
+            t: x.mul(&y),
+        })
+    }
+
+    pub fn compress(&self) -> [u8; 32] {
+        let z_inv = self.z.invert();
+        let x = self.x.mul(&z_inv);
+        let y = self.y.mul(&z_inv);
+
+        let mut bytes = y.to_bytes();
+        bytes[31] |= (x.is_negative() as u8) << 7;
+        bytes
+    }
+
+    pub fn neg(&self) -> Self {
+        Self {
+            x: self.x.neg(),
+            y: self.y,
+            z: self.z,
+            t: self.t.neg(),
+        }
+    }
+
+    // unified addition (RFC 8032 5.1.4), also valid for doubling
+    pub fn add(&self, rhs: &Self) -> Self {
+        let a = self.y.sub(&self.x).mul(&rhs.y.sub(&rhs.x));
+        let b = self.y.add(&self.x).mul(&rhs.y.add(&rhs.x));
+        let c = self.t.mul(&Self::D2).mul(&rhs.t);
+        let d = self.z.add(&self.z).mul(&rhs.z);
+        let e = b.sub(&a);
+        let f = d.sub(&c);
+        let g = d.add(&c);
+        let h = b.add(&a);
+
+        Self {
+            x: e.mul(&f),
+            y: g.mul(&h),
+            z: f.mul(&g),
+            t: e.mul(&h),
+        }
+    }
+
+    // [scalar]self for a little endian scalar, variable time
+    pub fn mul(&self, scalar: &[u8; 32]) -> Self {
+        let mut result = Self::IDENTITY;
+        for byte in scalar.iter().rev() {
+            for bit in (0..8).rev() {
+                result = result.add(&result);
+                if (byte >> bit) & 1 == 1 {
+                    result = result.add(self);
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,106 @@
+// Integers mod the group order L = 2^252 + 27742317777372353535851937790883648493,
+// as four little endian u64 words. Bit-at-a-time arithmetic, which is plenty for
+// one signature per boot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Scalar([u64; 4]);
+
+impl Scalar {
+    const L: [u64; 4] = [
+        0x5812631a5cf5d3ed,
+        0x14def9dea2f79cd6,
+        0x0000000000000000,
+        0x1000000000000000,
+    ];
+
+    const ZERO: Self = Self([0; 4]);
+
+    // any little endian integer, reduced mod L
+    pub fn from_bytes_mod_order(bytes: &[u8]) -> Self {
+        let mut result = Self::ZERO;
+        for byte in bytes.iter().rev() {
+            for bit in (0..8).rev() {
+                result = result.add(&result);
+                if (byte >> bit) & 1 == 1 {
+                    result = result.add(&Self([1, 0, 0, 0]));
+                }
+            }
+        }
+        result
+    }
+
+    // true if the little endian bytes are already below L, as required for S in signatures
+    pub fn is_canonical(bytes: &[u8; 32]) -> bool {
+        let value = Self::words(bytes);
+        Self::less_than(&value, &Self::L)
+    }
+
+    pub fn to_bytes(self) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (chunk, word) in bytes.chunks_exact_mut(8).zip(self.0) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    // both operands are below L < 2^253, so the sum cannot overflow 256 bits
+    pub fn add(&self, rhs: &Self) -> Self {
+        let mut sum = [0u64; 4];
+        let mut carry = false;
+        for (sum, (lhs, rhs)) in sum.iter_mut().zip(self.0.iter().zip(&rhs.0)) {
+            let (value, carry1) = lhs.overflowing_add(*rhs);
+            let (value, carry2) = value.overflowing_add(carry as u64);
+            *sum = value;
+            carry = carry1 || carry2;
+        }
+
+        if Self::less_than(&sum, &Self::L) {
+            Self(sum)
+        } else {
+            Self(Self::sub_words(&sum, &Self::L))
+        }
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let mut result = Self::ZERO;
+        for word in rhs.0.iter().rev() {
+            for bit in (0..64).rev() {
+                result = result.add(&result);
+                if (word >> bit) & 1 == 1 {
+                    result = result.add(self);
+                }
+            }
+        }
+        result
+    }
+
+    fn words(bytes: &[u8; 32]) -> [u64; 4] {
+        let mut words = [0u64; 4];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(8)) {
+            let mut le = [0u8; 8];
+            le.copy_from_slice(chunk);
+            *word = u64::from_le_bytes(le);
+        }
+        words
+    }
+
+    fn less_than(a: &[u64; 4], b: &[u64; 4]) -> bool {
+        for i in (0..4).rev() {
+            if a[i] != b[i] {
+                return a[i] < b[i];
+            }
+        }
+        false
+    }
+
+    fn sub_words(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+        let mut difference = [0u64; 4];
+        let mut borrow = false;
+        for i in 0..4 {
+            let (value, borrow1) = a[i].overflowing_sub(b[i]);
+            let (value, borrow2) = value.overflowing_sub(borrow as u64);
+            difference[i] = value;
+            borrow = borrow1 || borrow2;
+        }
+        difference
+    }
+}
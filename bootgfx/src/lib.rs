@@ -103,6 +103,18 @@ impl FrameBuffer {
         unsafe { slice::from_raw_parts_mut(self.base_ptr, self.scanline_pixels * self.y_pixels) }
     }
 
+    pub const fn mode(&self) -> FrameBufferMode {
+        self.mode
+    }
+
+    pub const fn base_ptr(&self) -> *mut u32 {
+        self.base_ptr
+    }
+
+    pub const fn scanline_pixels(&self) -> usize {
+        self.scanline_pixels
+    }
+
     pub const fn width(&self) -> usize {
         self.x_pixels
     }
@@ -22,8 +22,8 @@ impl Terminal {
     pub const CURSOR: Color = Color::new(0x00, 0xFF, 0xFF);
 
     pub fn new(frame_buffer: FrameBuffer) -> Self {
-        let width = frame_buffer.width() / 8;
-        let height = frame_buffer.height() / 16;
+        let width = (frame_buffer.width() / 8).min(Self::BUFF_WIDTH_MAX);
+        let height = (frame_buffer.height() / 16).min(Self::BUFF_HEIGHT_MAX);
 
         let mut terminal = Self {
             frame_buffer: frame_buffer,
@@ -0,0 +1,276 @@
+#![no_std]
+
+use core::marker::PhantomData;
+use core::slice;
+use core::str;
+
+// Handed from as-boot to the kernel in rdi.
+// All addresses are physical and identity-mapped when the kernel is entered.
+// The slice accessors are unsafe because the fields are public: only a BootInfo
+// as-boot filled in is known to point at valid memory.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct BootInfo {
+    pub magic: u64,
+    pub version: u32,
+    pub size: u32,
+    pub memory_map: BootSlice<MemoryRegion>,
+    pub frame_buffer: FrameBufferInfo,
+    pub kernel: KernelInfo,
+    pub rsdp: u64,
+    pub smbios: u64,
+    pub cmdline: BootSlice<u8>,
+    pub modules: BootSlice<Module>,
+    // virtual address of EFI_RUNTIME_SERVICES after SetVirtualAddressMap, 0 if unavailable.
+    // It is only valid while the runtime window set up by as-boot stays mapped.
+    pub runtime_services: u64,
+    // every processor the firmware knows of, empty if it has no MP services
+    pub cpus: BootSlice<CpuInfo>,
+}
+
+impl Default for BootInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BootInfo {
+    pub const MAGIC: u64 = u64::from_le_bytes(*b"ASBOOTIF");
+    pub const VERSION: u32 = 6;
+
+    pub const fn new() -> Self {
+        Self {
+            magic: Self::MAGIC,
+            version: Self::VERSION,
+            size: size_of::<Self>() as u32,
+            memory_map: BootSlice::empty(),
+            frame_buffer: FrameBufferInfo::empty(),
+            kernel: KernelInfo::empty(),
+            rsdp: 0,
+            smbios: 0,
+            cmdline: BootSlice::empty(),
+            modules: BootSlice::empty(),
+            runtime_services: 0,
+            cpus: BootSlice::empty(),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.magic == Self::MAGIC
+            && self.version == Self::VERSION
+            && self.size as usize == size_of::<Self>()
+    }
+
+    /// # Safety
+    ///
+    /// `memory_map` must meet the requirements of [`BootSlice::as_slice`].
+    pub unsafe fn memory_map(&self) -> &[MemoryRegion] {
+        unsafe { self.memory_map.as_slice() }
+    }
+
+    /// # Safety
+    ///
+    /// `cmdline` must meet the requirements of [`BootSlice::as_slice`].
+    pub unsafe fn cmdline(&self) -> &str {
+        let cmdline = unsafe { self.cmdline.as_slice() };
+        str::from_utf8(cmdline).unwrap_or("")
+    }
+
+    /// # Safety
+    ///
+    /// `modules` must meet the requirements of [`BootSlice::as_slice`].
+    pub unsafe fn modules(&self) -> &[Module] {
+        unsafe { self.modules.as_slice() }
+    }
+
+    /// # Safety
+    ///
+    /// `cpus` must meet the requirements of [`BootSlice::as_slice`].
+    pub unsafe fn cpus(&self) -> &[CpuInfo] {
+        unsafe { self.cpus.as_slice() }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct BootSlice<T> {
+    pub addr: u64,
+    pub len: u64,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> Clone for BootSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BootSlice<T> {}
+
+impl<T> BootSlice<T> {
+    pub const fn new(addr: u64, len: u64) -> Self {
+        Self {
+            addr,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub const fn empty() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub fn from_slice(slice: &[T]) -> Self {
+        Self::new(slice.as_ptr().addr() as u64, slice.len() as u64)
+    }
+
+    /// # Safety
+    ///
+    /// `addr` must point to `len` initialized values of T that stay valid and unmodified
+    /// for as long as the returned slice is used.
+    pub unsafe fn as_slice(&self) -> &[T] {
+        if self.addr == 0 || self.len == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.addr as usize as *const T, self.len as usize) }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub base: u64,
+    pub pages: u64,
+    pub kind: MemoryRegionKind,
+}
+
+impl MemoryRegion {
+    pub const PAGE_SIZE: u64 = 4096;
+
+    pub const fn end(&self) -> u64 {
+        self.base + self.pages * Self::PAGE_SIZE
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryRegionKind {
+    Usable = 0,
+    Reclaimable = 1,
+    Loader = 2,
+    Runtime = 3,
+    AcpiReclaimable = 4,
+    AcpiNvs = 5,
+    Mmio = 6,
+    Reserved = 7,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FrameBufferInfo {
+    pub base: u64,
+    pub width: u64,
+    pub height: u64,
+    pub stride: u64,
+    pub format: PixelFormat,
+}
+
+impl FrameBufferInfo {
+    pub const fn empty() -> Self {
+        Self {
+            base: 0,
+            width: 0,
+            height: 0,
+            stride: 0,
+            format: PixelFormat::Unknown,
+        }
+    }
+}
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgb = 0,
+    Bgr = 1,
+    Unknown = 2,
+}
+
+// A file loaded by as-boot next to the kernel (initrd, user programs, fonts...).
+// `base` is page-aligned and the memory is marked as Loader in the memory map.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Module {
+    pub base: u64,
+    pub size: u64,
+    pub name: BootSlice<u8>,
+}
+
+impl Module {
+    /// # Safety
+    ///
+    /// `name` must meet the requirements of [`BootSlice::as_slice`].
+    pub unsafe fn name(&self) -> &str {
+        let name = unsafe { self.name.as_slice() };
+        str::from_utf8(name).unwrap_or("")
+    }
+
+    /// # Safety
+    ///
+    /// `base` must point to `size` initialized bytes that stay valid and unmodified
+    /// for as long as the returned slice is used.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        if self.base == 0 || self.size == 0 {
+            &[]
+        } else {
+            unsafe { slice::from_raw_parts(self.base as usize as *const u8, self.size as usize) }
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct KernelInfo {
+    pub physical_base: u64,
+    pub virtual_base: u64,
+    pub size: u64,
+    // virtual_base minus the address the kernel was linked at, non-zero with KASLR
+    pub slide: u64,
+}
+
+impl KernelInfo {
+    pub const fn empty() -> Self {
+        Self {
+            physical_base: 0,
+            virtual_base: 0,
+            size: 0,
+            slide: 0,
+        }
+    }
+}
+
+// A logical processor as reported by EFI_MP_SERVICES_PROTOCOL. All APs are still halted
+// by the firmware when the kernel is entered and have to be started with INIT-SIPI-SIPI.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuInfo {
+    pub apic_id: u32,
+    pub package: u32,
+    pub core: u32,
+    pub thread: u32,
+    pub flags: u32,
+}
+
+impl CpuInfo {
+    pub const BSP: u32 = 1 << 0;
+    pub const ENABLED: u32 = 1 << 1;
+    pub const HEALTHY: u32 = 1 << 2;
+
+    pub const fn is_bsp(&self) -> bool {
+        self.flags & Self::BSP != 0
+    }
+
+    pub const fn is_enabled(&self) -> bool {
+        self.flags & Self::ENABLED != 0
+    }
+}
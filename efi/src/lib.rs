@@ -1,7 +1,12 @@
 #![no_std]
 
+mod protocol;
+mod status;
 pub mod utf16;
 
+pub use protocol::*;
+pub use status::*;
+
 use core::ffi::c_void;
 
 pub type Boolean = bool;
@@ -67,25 +72,75 @@ pub struct EfiConfigurationTable {
     pub vendor_table: *const c_void,
 }
 
+impl EfiConfigurationTable {
+    pub const ACPI_20_TABLE_GUID: EfiGuid = EfiGuid(
+        0x8868e871,
+        0xe4f1,
+        0x11d3,
+        [0xbc, 0x22, 0x00, 0x80, 0xc7, 0x3c, 0x88, 0x81],
+    );
+    pub const ACPI_TABLE_GUID: EfiGuid = EfiGuid(
+        0xeb9d2d30,
+        0x2d88,
+        0x11d3,
+        [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+    );
+    pub const SMBIOS_TABLE_GUID: EfiGuid = EfiGuid(
+        0xeb9d2d31,
+        0x2d88,
+        0x11d3,
+        [0x9a, 0x16, 0x00, 0x90, 0x27, 0x3f, 0xc1, 0x4d],
+    );
+    pub const SMBIOS3_TABLE_GUID: EfiGuid = EfiGuid(
+        0xf2fd1544,
+        0x9794,
+        0x4a2c,
+        [0x99, 0x2e, 0xe5, 0xbb, 0xcf, 0x20, 0xe3, 0x94],
+    );
+    pub const MEMORY_ATTRIBUTES_TABLE_GUID: EfiGuid = EfiGuid(
+        0xdcfa911d,
+        0x26eb,
+        0x469f,
+        [0xa2, 0x20, 0x38, 0xb7, 0xdc, 0x46, 0x12, 0x88],
+    );
+}
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct EfiInputKey {
-    scan_code: UInt16,
-    unicode_char: Char16,
+    pub scan_code: UInt16,
+    pub unicode_char: Char16,
+}
+
+impl EfiInputKey {
+    pub const SCAN_NULL: UInt16 = 0x0000;
+    pub const SCAN_UP: UInt16 = 0x0001;
+    pub const SCAN_DOWN: UInt16 = 0x0002;
+    pub const SCAN_RIGHT: UInt16 = 0x0003;
+    pub const SCAN_LEFT: UInt16 = 0x0004;
+    pub const SCAN_HOME: UInt16 = 0x0005;
+    pub const SCAN_END: UInt16 = 0x0006;
+    pub const SCAN_DELETE: UInt16 = 0x0008;
+    pub const SCAN_ESC: UInt16 = 0x0017;
+
+    pub const CHAR_BACKSPACE: Char16 = 0x0008;
+    pub const CHAR_TAB: Char16 = 0x0009;
+    pub const CHAR_LINEFEED: Char16 = 0x000a;
+    pub const CHAR_CARRIAGE_RETURN: Char16 = 0x000d;
 }
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiSimpleTextInputProtocol {
-    reset: unsafe extern "efiapi" fn(
+    pub reset: unsafe extern "efiapi" fn(
         this: *const EfiSimpleTextInputProtocol,
         extended_verification: Boolean,
     ) -> EfiStatus,
-    read_key_stroke: unsafe extern "efiapi" fn(
+    pub read_key_stroke: unsafe extern "efiapi" fn(
         this: *const EfiSimpleTextInputProtocol,
         key: *mut EfiInputKey,
     ) -> EfiStatus,
-    wait_for_key: EfiEvent,
+    pub wait_for_key: EfiEvent,
 }
 
 impl EfiSimpleTextInputProtocol {
@@ -207,12 +262,15 @@ pub struct SimpleTextOutputMode {
 pub struct EfiRuntimeServices {
     hdr: EfiTableHeader,
 
-    get_time: *const c_void,
+    pub get_time: unsafe extern "efiapi" fn(
+        time: *mut EfiTime,
+        capabilities: *mut EfiTimeCapabilities,
+    ) -> EfiStatus,
     set_time: *const c_void,
     get_wakeup_time: *const c_void,
     set_wakeup_time: *const c_void,
 
-    set_virtual_address_map: unsafe extern "efiapi" fn(
+    pub set_virtual_address_map: unsafe extern "efiapi" fn(
         memory_map_size: UIntN,
         descriptor_size: UIntN,
         descriptor_version: UIntN,
@@ -221,28 +279,28 @@ pub struct EfiRuntimeServices {
     convert_pointer:
         unsafe extern "efiapi" fn(debug_disposition: UIntN, address: *mut *mut c_void) -> EfiStatus,
 
-    get_variable: unsafe extern "efiapi" fn(
+    pub get_variable: unsafe extern "efiapi" fn(
         variable_name: *const Char16,
         vendor_guid: *const EfiGuid,
         attributes: *mut UInt32,
         data_size: *mut UIntN,
         data: *mut c_void,
     ) -> EfiStatus,
-    get_next_variable_name: unsafe extern "efiapi" fn(
+    pub get_next_variable_name: unsafe extern "efiapi" fn(
         variable_name_size: *mut UIntN,
         variable_name: *mut Char16,
         vendor_guid: *mut EfiGuid,
     ) -> EfiStatus,
-    set_variable: unsafe extern "efiapi" fn(
+    pub set_variable: unsafe extern "efiapi" fn(
         variable_name: *const Char16,
         vendor_guid: *const EfiGuid,
-        attributes: *const UInt32,
+        attributes: UInt32,
         data_size: UIntN,
         data: *const c_void,
     ) -> EfiStatus,
 
     get_next_high_monotonic_count: *const c_void,
-    reset_system: unsafe extern "efiapi" fn(
+    pub reset_system: unsafe extern "efiapi" fn(
         reset_type: EfiResetType,
         reset_status: EfiStatus,
         data_size: UIntN,
@@ -253,6 +311,23 @@ pub struct EfiRuntimeServices {
     query_variable_info: *const c_void,
 }
 
+impl EfiRuntimeServices {
+    pub const VARIABLE_NON_VOLATILE: UInt32 = 0x00000001;
+    pub const VARIABLE_BOOTSERVICE_ACCESS: UInt32 = 0x00000002;
+    pub const VARIABLE_RUNTIME_ACCESS: UInt32 = 0x00000004;
+    pub const VARIABLE_HARDWARE_ERROR_RECORD: UInt32 = 0x00000008;
+    pub const VARIABLE_TIME_BASED_AUTHENTICATED_WRITE_ACCESS: UInt32 = 0x00000020;
+    pub const VARIABLE_APPEND_WRITE: UInt32 = 0x00000040;
+
+    // vendor GUID of the architectural variables (SecureBoot, SetupMode, BootOrder...)
+    pub const GLOBAL_VARIABLE_GUID: EfiGuid = EfiGuid(
+        0x8be4df61,
+        0x93ca,
+        0x11d2,
+        [0xaa, 0x0d, 0x00, 0xe0, 0x98, 0x03, 0x2b, 0x8c],
+    );
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub enum EfiResetType {
@@ -285,14 +360,32 @@ pub struct EfiBootServices {
         descriptor_size: *mut UIntN,
         descriptor_version: *mut UInt32,
     ) -> EfiStatus,
-    allocate_pool: *const c_void,
-    free_pool: *const c_void,
-
-    create_event: *const c_void,
-    set_timer: *const c_void,
-    wait_for_event: *const c_void,
+    pub allocate_pool: unsafe extern "efiapi" fn(
+        pool_type: EfiMemoryType,
+        size: UIntN,
+        buffer: *mut *mut c_void,
+    ) -> EfiStatus,
+    pub free_pool: unsafe extern "efiapi" fn(buffer: *mut c_void) -> EfiStatus,
+
+    pub create_event: unsafe extern "efiapi" fn(
+        r#type: UInt32,
+        notify_tpl: EfiTpl,
+        notify_function: Option<EfiEventNotify>,
+        notify_context: *const c_void,
+        event: *mut EfiEvent,
+    ) -> EfiStatus,
+    pub set_timer: unsafe extern "efiapi" fn(
+        event: EfiEvent,
+        r#type: EfiTimerDelay,
+        trigger_time: UInt64,
+    ) -> EfiStatus,
+    pub wait_for_event: unsafe extern "efiapi" fn(
+        number_of_events: UIntN,
+        event: *const EfiEvent,
+        index: *mut UIntN,
+    ) -> EfiStatus,
     signal_event: *const c_void,
-    close_event: *const c_void,
+    pub close_event: unsafe extern "efiapi" fn(event: EfiEvent) -> EfiStatus,
     check_event: *const c_void,
 
     install_protocol_interface: *const c_void,
@@ -305,7 +398,7 @@ pub struct EfiBootServices {
     ) -> EfiStatus,
     _reserved: *const c_void,
     register_protocol_notify: *const c_void,
-    locate_handle: unsafe extern "efiapi" fn(
+    pub locate_handle: unsafe extern "efiapi" fn(
         search_type: EfiLocateSearchType,
         protocol: *const EfiGuid,
         search_key: *const c_void,
@@ -315,21 +408,37 @@ pub struct EfiBootServices {
     locate_device_path: *const c_void,
     install_configurtaion_table: *const c_void,
 
-    load_image: *const c_void,
-    start_image: *const c_void,
-    exit: *const c_void,
-    unload_image: *const c_void,
+    pub load_image: unsafe extern "efiapi" fn(
+        boot_policy: Boolean,
+        parent_image_handle: EfiHandle,
+        device_path: *const c_void,
+        source_buffer: *const c_void,
+        source_size: UIntN,
+        image_handle: *mut EfiHandle,
+    ) -> EfiStatus,
+    pub start_image: unsafe extern "efiapi" fn(
+        image_handle: EfiHandle,
+        exit_data_size: *mut UIntN,
+        exit_data: *mut *mut Char16,
+    ) -> EfiStatus,
+    pub exit: unsafe extern "efiapi" fn(
+        image_handle: EfiHandle,
+        exit_status: EfiStatus,
+        exit_data_size: UIntN,
+        exit_data: *const Char16,
+    ) -> EfiStatus,
+    pub unload_image: unsafe extern "efiapi" fn(image_handle: EfiHandle) -> EfiStatus,
     pub exit_boot_services:
         unsafe extern "efiapi" fn(image_handle: EfiHandle, map_key: UIntN) -> EfiStatus,
 
     get_next_monotonic_count: *const c_void,
-    stall: *const c_void,
+    pub stall: unsafe extern "efiapi" fn(microseconds: UIntN) -> EfiStatus,
     set_watchdog_timer: *const c_void,
 
     connect_controller: *const c_void,
     disconnect_controller: *const c_void,
 
-    open_protocol: unsafe extern "efiapi" fn(
+    pub open_protocol: unsafe extern "efiapi" fn(
         handle: EfiHandle,
         protocol: *const EfiGuid,
         interface: *mut *const c_void,
@@ -337,11 +446,11 @@ pub struct EfiBootServices {
         controller_handle: EfiHandle,
         attributes: UInt32,
     ) -> EfiStatus,
-    close_protocol: unsafe extern "efiapi" fn(
+    pub close_protocol: unsafe extern "efiapi" fn(
         handle: EfiHandle,
         protocol: *const EfiGuid,
         agent_handle: EfiHandle,
-        controller_handle: *const EfiHandle,
+        controller_handle: EfiHandle,
     ) -> EfiStatus,
     open_protocol_information: *const c_void,
 
@@ -362,6 +471,27 @@ pub struct EfiBootServices {
     create_event_ex: *const c_void,
 }
 
+impl EfiBootServices {
+    pub const EVT_TIMER: UInt32 = 0x80000000;
+    pub const EVT_RUNTIME: UInt32 = 0x40000000;
+    pub const EVT_NOTIFY_WAIT: UInt32 = 0x00000100;
+    pub const EVT_NOTIFY_SIGNAL: UInt32 = 0x00000200;
+
+    pub const TPL_APPLICATION: EfiTpl = 4;
+    pub const TPL_CALLBACK: EfiTpl = 8;
+    pub const TPL_NOTIFY: EfiTpl = 16;
+}
+
+pub type EfiEventNotify = unsafe extern "efiapi" fn(event: EfiEvent, context: *const c_void);
+
+#[repr(u32)]
+#[derive(Clone, Copy, Debug)]
+pub enum EfiTimerDelay {
+    TimerCancel = 0,
+    TimerPeriodic = 1,
+    TimerRelative = 2,
+}
+
 #[repr(u32)]
 #[derive(Clone, Copy, Debug)]
 pub enum EfiLocateSearchType {
@@ -380,7 +510,7 @@ pub enum EfiAllocateType {
 }
 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EfiMemoryType {
     EfiReservedMemoryType = 0,
     EfiLoaderCode = 1,
@@ -404,10 +534,34 @@ pub enum EfiMemoryType {
 pub type EfiPhysicalAddress = UInt64;
 pub type EfiVirtualAddress = UInt64;
 
+impl EfiMemoryType {
+    pub fn from_raw(raw: UInt32) -> Option<Self> {
+        match raw {
+            0 => Some(Self::EfiReservedMemoryType),
+            1 => Some(Self::EfiLoaderCode),
+            2 => Some(Self::EfiLoaderData),
+            3 => Some(Self::EfiBootServicesCode),
+            4 => Some(Self::EfiBootServicesData),
+            5 => Some(Self::EfiRuntimeServicesCode),
+            6 => Some(Self::EfiRuntimeServicesData),
+            7 => Some(Self::EfiConventionalMemory),
+            8 => Some(Self::EfiUnusableMemory),
+            9 => Some(Self::EfiACPIReclaimMemory),
+            10 => Some(Self::EfiACPIMemoryNVS),
+            11 => Some(Self::EfiMemoryMappedIO),
+            12 => Some(Self::EfiMemoryMappedIOPortSpace),
+            13 => Some(Self::EfiPalCode),
+            14 => Some(Self::EfiPersistentMemory),
+            15 => Some(Self::EfiUnacceptedMemoryType),
+            _ => None,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiMemoryDescriptor {
-    r#type: EfiMemoryType,
+    r#type: UInt32,
     physical_start: EfiPhysicalAddress,
     virtual_start: EfiVirtualAddress,
     number_of_pages: UInt64,
@@ -415,6 +569,40 @@ pub struct EfiMemoryDescriptor {
 }
 
 impl EfiMemoryDescriptor {
+    pub const PAGE_SIZE: UInt64 = 4096;
+
+    pub fn memory_type(&self) -> Option<EfiMemoryType> {
+        EfiMemoryType::from_raw(self.r#type)
+    }
+
+    pub fn raw_memory_type(&self) -> UInt32 {
+        self.r#type
+    }
+
+    pub fn physical_start(&self) -> EfiPhysicalAddress {
+        self.physical_start
+    }
+
+    pub fn physical_end(&self) -> EfiPhysicalAddress {
+        self.physical_start + self.number_of_pages * Self::PAGE_SIZE
+    }
+
+    pub fn virtual_start(&self) -> EfiVirtualAddress {
+        self.virtual_start
+    }
+
+    pub fn set_virtual_start(&mut self, virtual_start: EfiVirtualAddress) {
+        self.virtual_start = virtual_start;
+    }
+
+    pub fn number_of_pages(&self) -> UInt64 {
+        self.number_of_pages
+    }
+
+    pub fn attribute(&self) -> UInt64 {
+        self.attribute
+    }
+
     pub const EFI_MEMORY_UC: UInt64 = 0x0000000000000001;
     pub const EFI_MEMORY_WC: UInt64 = 0x0000000000000002;
     pub const EFI_MEMORY_WT: UInt64 = 0x0000000000000004;
@@ -433,6 +621,18 @@ impl EfiMemoryDescriptor {
     pub const EFI_MEMORY_ISA_MASK: UInt64 = 0x0FFFF00000000000;
 }
 
+// Header of EFI_MEMORY_ATTRIBUTES_TABLE, followed by `number_of_entries` descriptors of
+// `descriptor_size` bytes. They split the runtime regions of each image into code
+// (EFI_MEMORY_RO) and data (EFI_MEMORY_XP).
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EfiMemoryAttributesTable {
+    pub version: UInt32,
+    pub number_of_entries: UInt32,
+    pub descriptor_size: UInt32,
+    pub flags: UInt32,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiSimpleFileSystemProtocol {
@@ -464,27 +664,34 @@ pub struct EfiFileProtocol {
         attributes: UInt64,
     ) -> EfiStatus,
     pub close: unsafe extern "efiapi" fn(this: *const EfiFileProtocol) -> EfiStatus,
-    delete: unsafe extern "efiapi" fn(this: *const EfiFileProtocol) -> EfiStatus,
+    pub delete: unsafe extern "efiapi" fn(this: *const EfiFileProtocol) -> EfiStatus,
     pub read: unsafe extern "efiapi" fn(
         this: *const EfiFileProtocol,
         buffer_size: *mut UIntN,
         buffer: *mut u8,
     ) -> EfiStatus,
-    write: unsafe extern "efiapi" fn(
+    pub write: unsafe extern "efiapi" fn(
         this: *const EfiFileProtocol,
         buffer_size: *mut UIntN,
         buffer: *const u8,
     ) -> EfiStatus,
-    get_position: usize,
-    set_position: usize,
+    pub get_position:
+        unsafe extern "efiapi" fn(this: *const EfiFileProtocol, position: *mut UInt64) -> EfiStatus,
+    pub set_position:
+        unsafe extern "efiapi" fn(this: *const EfiFileProtocol, position: UInt64) -> EfiStatus,
     pub get_info: unsafe extern "efiapi" fn(
         this: *const EfiFileProtocol,
         information_type: *const EfiGuid,
         buffer_size: *mut UIntN,
         buffer: *mut u8,
     ) -> EfiStatus,
-    set_info: usize,
-    flush: usize,
+    pub set_info: unsafe extern "efiapi" fn(
+        this: *const EfiFileProtocol,
+        information_type: *const EfiGuid,
+        buffer_size: UIntN,
+        buffer: *const u8,
+    ) -> EfiStatus,
+    pub flush: unsafe extern "efiapi" fn(this: *const EfiFileProtocol) -> EfiStatus,
     open_ex: usize,
     read_ex: usize,
     write_ex: usize,
@@ -496,6 +703,9 @@ impl EfiFileProtocol {
     pub const EFI_FILE_MODE_WRITE: UInt64 = 0x0000000000000002;
     pub const EFI_FILE_MODE_CREATE: UInt64 = 0x8000000000000000;
 
+    // set_position to this moves to the end of the file
+    pub const POSITION_END: UInt64 = UInt64::MAX;
+
     pub const EFI_FILE_READ_ONLY: UInt64 = 0x0000000000000001;
     pub const EFI_FILE_HIDDEN: UInt64 = 0x0000000000000002;
     pub const EFI_FILE_SYSTEM: UInt64 = 0x0000000000000004;
@@ -505,6 +715,7 @@ impl EfiFileProtocol {
     pub const EFI_FILE_VALID_ATTR: UInt64 = 0x0000000000000037;
 }
 
+// followed by the null-terminated UTF-16 file name
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiFileInfo {
@@ -537,19 +748,31 @@ impl EfiFileInfo {
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiTime {
-    year: UInt16,
-    month: UInt8,
-    day: UInt8,
-    hour: UInt8,
-    minute: UInt8,
-    second: UInt8,
+    pub year: UInt16,
+    pub month: UInt8,
+    pub day: UInt8,
+    pub hour: UInt8,
+    pub minute: UInt8,
+    pub second: UInt8,
     pad1: UInt8,
-    nanosecond: UInt32,
-    time_zone: Int16,
-    day_light: UInt8,
+    pub nanosecond: UInt32,
+    pub time_zone: Int16,
+    pub day_light: UInt8,
     pad2: UInt8,
 }
 
+impl EfiTime {
+    pub const UNSPECIFIED_TIMEZONE: Int16 = 0x07ff;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EfiTimeCapabilities {
+    pub resolution: UInt32,
+    pub accuracy: UInt32,
+    pub sets_to_zero: Boolean,
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub struct EfiLoadedImageProtocol {
@@ -561,8 +784,8 @@ pub struct EfiLoadedImageProtocol {
     file_path: usize,
     reserved: usize,
 
-    load_options_size: UInt32,
-    load_options: usize,
+    pub load_options_size: UInt32,
+    pub load_options: *const c_void,
 
     image_base: *const u8,
     image_size: UInt64,
@@ -629,7 +852,7 @@ pub struct EfiGraphicsOutputModeInformation {
 }
 
 #[repr(u32)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EfiGraphicsPixelFormat {
     PixelRedGreenBlueReserved8BitPerColor = 0,
     PixelBlueGreenRedReserved8BitPerColor = 1,
@@ -646,3 +869,95 @@ pub struct EfiPixelBitmask {
     blue_mask: UInt32,
     reserved_mask: UInt32,
 }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EfiRngProtocol {
+    get_info: usize,
+    // a null `rng_algorithm` selects the firmware's default algorithm
+    pub get_rng: unsafe extern "efiapi" fn(
+        this: *const EfiRngProtocol,
+        rng_algorithm: *const EfiGuid,
+        rng_value_length: UIntN,
+        rng_value: *mut UInt8,
+    ) -> EfiStatus,
+}
+
+impl EfiRngProtocol {
+    pub const GUID: EfiGuid = EfiGuid(
+        0x3152bca5,
+        0xeade,
+        0x433d,
+        [0x86, 0x2e, 0xc0, 0x1c, 0xdc, 0x29, 0x1f, 0x44],
+    );
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EfiMpServicesProtocol {
+    pub get_number_of_processors: unsafe extern "efiapi" fn(
+        this: *const EfiMpServicesProtocol,
+        number_of_processors: *mut UIntN,
+        number_of_enabled_processors: *mut UIntN,
+    ) -> EfiStatus,
+    pub get_processor_info: unsafe extern "efiapi" fn(
+        this: *const EfiMpServicesProtocol,
+        processor_number: UIntN,
+        processor_info_buffer: *mut EfiProcessorInformation,
+    ) -> EfiStatus,
+    startup_all_aps: usize,
+    startup_this_ap: usize,
+    switch_bsp: usize,
+    enable_disable_ap: usize,
+    pub who_am_i: unsafe extern "efiapi" fn(
+        this: *const EfiMpServicesProtocol,
+        processor_number: *mut UIntN,
+    ) -> EfiStatus,
+}
+
+impl EfiMpServicesProtocol {
+    pub const GUID: EfiGuid = EfiGuid(
+        0x3fdda605,
+        0xa76e,
+        0x4f46,
+        [0xad, 0x29, 0x12, 0xf4, 0x53, 0x1b, 0x3d, 0x08],
+    );
+
+    // or'ed into the processor number of get_processor_info to fill extended_information
+    pub const CPU_V2_EXTENDED_TOPOLOGY: UIntN = 1 << 24;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct EfiProcessorInformation {
+    // the local APIC ID on x64
+    pub processor_id: UInt64,
+    pub status_flag: UInt32,
+    pub location: EfiCpuPhysicalLocation,
+    pub extended_information: EfiCpuPhysicalLocation2,
+}
+
+impl EfiProcessorInformation {
+    pub const PROCESSOR_AS_BSP_BIT: UInt32 = 0x00000001;
+    pub const PROCESSOR_ENABLED_BIT: UInt32 = 0x00000002;
+    pub const PROCESSOR_HEALTH_STATUS_BIT: UInt32 = 0x00000004;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EfiCpuPhysicalLocation {
+    pub package: UInt32,
+    pub core: UInt32,
+    pub thread: UInt32,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EfiCpuPhysicalLocation2 {
+    pub package: UInt32,
+    pub module: UInt32,
+    pub tile: UInt32,
+    pub die: UInt32,
+    pub core: UInt32,
+    pub thread: UInt32,
+}
@@ -0,0 +1,212 @@
+use super::*;
+use core::mem;
+use core::ops::Deref;
+use core::ptr;
+
+/// A protocol interface struct that can be looked up through boot services by its GUID.
+///
+/// # Safety
+///
+/// GUID must identify an interface whose layout is exactly Self.
+pub unsafe trait Protocol {
+    const GUID: EfiGuid;
+}
+
+unsafe impl Protocol for EfiSimpleTextInputProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiSimpleTextOutputProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiSimpleFileSystemProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiLoadedImageProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiGraphicsOutputProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiRngProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+unsafe impl Protocol for EfiMpServicesProtocol {
+    const GUID: EfiGuid = Self::GUID;
+}
+
+impl EfiBootServices {
+    pub const OPEN_PROTOCOL_BY_HANDLE_PROTOCOL: UInt32 = 0x00000001;
+    pub const OPEN_PROTOCOL_GET_PROTOCOL: UInt32 = 0x00000002;
+    pub const OPEN_PROTOCOL_TEST_PROTOCOL: UInt32 = 0x00000004;
+    pub const OPEN_PROTOCOL_BY_CHILD_CONTROLLER: UInt32 = 0x00000008;
+    pub const OPEN_PROTOCOL_BY_DRIVER: UInt32 = 0x00000010;
+    pub const OPEN_PROTOCOL_EXCLUSIVE: UInt32 = 0x00000020;
+
+    // first instance of P in the handle database
+    pub fn locate<P: Protocol>(&self) -> EfiResult<&P> {
+        let mut interface: *const c_void = ptr::null();
+        status_to_result(unsafe {
+            (self.locate_protocol)(&P::GUID, ptr::null(), &raw mut interface)
+        })?;
+
+        unsafe { Self::interface_ref(interface) }
+    }
+
+    /// # Safety
+    ///
+    /// `handle` must be a handle returned by the firmware that is still installed.
+    pub unsafe fn handle_protocol<P: Protocol>(&self, handle: EfiHandle) -> EfiResult<&P> {
+        let mut interface: *const c_void = ptr::null();
+        status_to_result(unsafe { (self.handle_protocol)(handle, &P::GUID, &raw mut interface) })?;
+
+        unsafe { Self::interface_ref(interface) }
+    }
+
+    /// For interfaces whose fields the caller is meant to fill in (e.g. LoadOptions of a
+    /// loaded image). The interface is returned as a raw pointer because the firmware hands
+    /// the same one to every caller.
+    ///
+    /// # Safety
+    ///
+    /// `handle` must be a handle returned by the firmware that is still installed.
+    pub unsafe fn handle_protocol_mut<P: Protocol>(&self, handle: EfiHandle) -> EfiResult<*mut P> {
+        let mut interface: *mut c_void = ptr::null_mut();
+        status_to_result(unsafe {
+            (self.handle_protocol)(handle, &P::GUID, &raw mut interface as *mut *const c_void)
+        })?;
+
+        if interface.is_null() {
+            Err(EfiError::NotFound)
+        } else {
+            Ok(interface as *mut P)
+        }
+    }
+
+    // how many handles support P, to size the buffer for locate_handles
+    pub fn handle_count<P: Protocol>(&self) -> EfiResult<usize> {
+        let mut buffer_size: UIntN = 0;
+        let status = unsafe {
+            (self.locate_handle)(
+                EfiLocateSearchType::ByProtocol,
+                &P::GUID,
+                ptr::null(),
+                &raw mut buffer_size,
+                ptr::null_mut(),
+            )
+        };
+        match status_to_result(status) {
+            Ok(()) | Err(EfiError::NotFound) => Ok(0),
+            Err(EfiError::BufferTooSmall) => Ok(buffer_size / size_of::<EfiHandle>()),
+            Err(error) => Err(error),
+        }
+    }
+
+    // every handle that supports P, returns how many were written to `handles`
+    pub fn locate_handles<P: Protocol>(&self, handles: &mut [EfiHandle]) -> EfiResult<usize> {
+        let mut buffer_size: UIntN = mem::size_of_val(handles);
+        status_to_result(unsafe {
+            (self.locate_handle)(
+                EfiLocateSearchType::ByProtocol,
+                &P::GUID,
+                ptr::null(),
+                &raw mut buffer_size,
+                handles.as_mut_ptr(),
+            )
+        })?;
+
+        Ok(buffer_size / size_of::<EfiHandle>())
+    }
+
+    /// Opens P on `handle` for `agent`; the protocol is closed again when the guard is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `handle` and `agent` must be handles returned by the firmware that stay installed
+    /// while the guard is alive.
+    pub unsafe fn open_protocol<P: Protocol>(
+        &self,
+        handle: EfiHandle,
+        agent: EfiHandle,
+        attributes: UInt32,
+    ) -> EfiResult<ProtocolGuard<'_, P>> {
+        let mut interface: *const c_void = ptr::null();
+        status_to_result(unsafe {
+            (self.open_protocol)(
+                handle,
+                &P::GUID,
+                &raw mut interface,
+                agent,
+                ptr::null_mut(),
+                attributes,
+            )
+        })?;
+
+        // the protocol is open even without an interface, so close it before giving up
+        let interface = match unsafe { Self::interface_ref(interface) } {
+            Ok(interface) => interface,
+            Err(error) => {
+                let _ = unsafe { self.close_protocol::<P>(handle, agent) };
+                return Err(error);
+            }
+        };
+        Ok(ProtocolGuard {
+            boot_services: self,
+            interface: interface,
+            handle: handle,
+            agent: agent,
+        })
+    }
+
+    /// # Safety
+    ///
+    /// `handle` and `agent` must be handles returned by the firmware that are still installed.
+    pub unsafe fn close_protocol<P: Protocol>(
+        &self,
+        handle: EfiHandle,
+        agent: EfiHandle,
+    ) -> EfiResult<()> {
+        status_to_result(unsafe { (self.close_protocol)(handle, &P::GUID, agent, ptr::null_mut()) })
+    }
+
+    unsafe fn interface_ref<'a, P>(interface: *const c_void) -> EfiResult<&'a P> {
+        unsafe { (interface as *const P).as_ref() }.ok_or(EfiError::NotFound)
+    }
+}
+
+#[derive(Debug)]
+pub struct ProtocolGuard<'a, P: Protocol> {
+    boot_services: &'a EfiBootServices,
+    interface: &'a P,
+    handle: EfiHandle,
+    agent: EfiHandle,
+}
+
+impl<'a, P: Protocol> ProtocolGuard<'a, P> {
+    pub fn handle(&self) -> EfiHandle {
+        self.handle
+    }
+}
+
+impl<'a, P: Protocol> Deref for ProtocolGuard<'a, P> {
+    type Target = P;
+
+    fn deref(&self) -> &P {
+        self.interface
+    }
+}
+
+impl<'a, P: Protocol> Drop for ProtocolGuard<'a, P> {
+    fn drop(&mut self) {
+        // open_protocol required both handles to outlive the guard
+        let _ = unsafe {
+            self.boot_services
+                .close_protocol::<P>(self.handle, self.agent)
+        };
+    }
+}
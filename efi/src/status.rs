@@ -0,0 +1,120 @@
+use super::EFI_STATUS_SUCCESS;
+use super::EfiStatus;
+use core::fmt;
+
+pub type EfiResult<T> = Result<T, EfiError>;
+
+const ERROR_BIT: EfiStatus = 1 << (EfiStatus::BITS - 1);
+
+const fn error(code: EfiStatus) -> EfiStatus {
+    ERROR_BIT | code
+}
+
+const fn warning(code: EfiStatus) -> EfiStatus {
+    code
+}
+
+// Declares the EFI_STATUS_* constants and the matching EfiError variants in one place.
+macro_rules! status_codes {
+    ($($variant:ident = $constant:ident = $status:expr, $description:literal;)*) => {
+        $(pub const $constant: EfiStatus = $status;)*
+
+        // Every non-success status from UEFI spec appendix D, including warnings.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum EfiError {
+            $($variant,)*
+            Unknown(EfiStatus),
+        }
+
+        impl EfiError {
+            pub const fn from_status(status: EfiStatus) -> Option<Self> {
+                match status {
+                    EFI_STATUS_SUCCESS => None,
+                    $($constant => Some(Self::$variant),)*
+                    _ => Some(Self::Unknown(status)),
+                }
+            }
+
+            pub const fn status(&self) -> EfiStatus {
+                match self {
+                    $(Self::$variant => $constant,)*
+                    Self::Unknown(status) => *status,
+                }
+            }
+
+            pub const fn description(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $description,)*
+                    Self::Unknown(_) => "unknown status",
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    LoadError = EFI_STATUS_LOAD_ERROR = error(1), "image failed to load";
+    InvalidParameter = EFI_STATUS_INVALID_PARAMETER = error(2), "invalid parameter";
+    Unsupported = EFI_STATUS_UNSUPPORTED = error(3), "operation is not supported";
+    BadBufferSize = EFI_STATUS_BAD_BUFFER_SIZE = error(4), "bad buffer size";
+    BufferTooSmall = EFI_STATUS_BUFFER_TOO_SMALL = error(5), "buffer too small";
+    NotReady = EFI_STATUS_NOT_READY = error(6), "no data pending";
+    DeviceError = EFI_STATUS_DEVICE_ERROR = error(7), "device error";
+    WriteProtected = EFI_STATUS_WRITE_PROTECTED = error(8), "device is write protected";
+    OutOfResources = EFI_STATUS_OUT_OF_RESOURCES = error(9), "out of resources";
+    VolumeCorrupted = EFI_STATUS_VOLUME_CORRUPTED = error(10), "volume is corrupted";
+    VolumeFull = EFI_STATUS_VOLUME_FULL = error(11), "volume is full";
+    NoMedia = EFI_STATUS_NO_MEDIA = error(12), "no media";
+    MediaChanged = EFI_STATUS_MEDIA_CHANGED = error(13), "media changed";
+    NotFound = EFI_STATUS_NOT_FOUND = error(14), "not found";
+    AccessDenied = EFI_STATUS_ACCESS_DENIED = error(15), "access denied";
+    NoResponse = EFI_STATUS_NO_RESPONSE = error(16), "no response";
+    NoMapping = EFI_STATUS_NO_MAPPING = error(17), "no mapping";
+    Timeout = EFI_STATUS_TIMEOUT = error(18), "timeout";
+    NotStarted = EFI_STATUS_NOT_STARTED = error(19), "protocol not started";
+    AlreadyStarted = EFI_STATUS_ALREADY_STARTED = error(20), "protocol already started";
+    Aborted = EFI_STATUS_ABORTED = error(21), "aborted";
+    IcmpError = EFI_STATUS_ICMP_ERROR = error(22), "ICMP error";
+    TftpError = EFI_STATUS_TFTP_ERROR = error(23), "TFTP error";
+    ProtocolError = EFI_STATUS_PROTOCOL_ERROR = error(24), "protocol error";
+    IncompatibleVersion = EFI_STATUS_INCOMPATIBLE_VERSION = error(25), "incompatible version";
+    SecurityViolation = EFI_STATUS_SECURITY_VIOLATION = error(26), "security violation";
+    CrcError = EFI_STATUS_CRC_ERROR = error(27), "CRC error";
+    EndOfMedia = EFI_STATUS_END_OF_MEDIA = error(28), "end of media";
+    EndOfFile = EFI_STATUS_END_OF_FILE = error(31), "end of file";
+    InvalidLanguage = EFI_STATUS_INVALID_LANGUAGE = error(32), "invalid language";
+    CompromisedData = EFI_STATUS_COMPROMISED_DATA = error(33), "compromised data";
+    IpAddressConflict = EFI_STATUS_IP_ADDRESS_CONFLICT = error(34), "IP address conflict";
+    HttpError = EFI_STATUS_HTTP_ERROR = error(35), "HTTP error";
+
+    WarnUnknownGlyph = EFI_STATUS_WARN_UNKNOWN_GLYPH = warning(1), "unknown glyph";
+    WarnDeleteFailure = EFI_STATUS_WARN_DELETE_FAILURE = warning(2), "file was closed but not deleted";
+    WarnWriteFailure = EFI_STATUS_WARN_WRITE_FAILURE = warning(3), "file was closed but not written";
+    WarnBufferTooSmall = EFI_STATUS_WARN_BUFFER_TOO_SMALL = warning(4), "buffer too small, data was truncated";
+    WarnStaleData = EFI_STATUS_WARN_STALE_DATA = warning(5), "stale data";
+    WarnFileSystem = EFI_STATUS_WARN_FILE_SYSTEM = warning(6), "buffer contains a file system";
+    WarnResetRequired = EFI_STATUS_WARN_RESET_REQUIRED = warning(7), "reset required";
+}
+
+impl EfiError {
+    pub const fn is_warning(&self) -> bool {
+        self.status() & ERROR_BIT == 0
+    }
+}
+
+impl fmt::Display for EfiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unknown(status) => write!(f, "{} {:#x}", self.description(), status),
+            _ => write!(f, "{}", self.description()),
+        }
+    }
+}
+
+// Warnings count as errors too: none of the callers expect a partially completed call.
+pub fn status_to_result(status: EfiStatus) -> EfiResult<()> {
+    match EfiError::from_status(status) {
+        None => Ok(()),
+        Some(error) => Err(error),
+    }
+}
@@ -5,6 +5,7 @@ pub mod types;
 use core::iter::Iterator;
 use core::mem;
 use core::mem::size_of;
+use core::ops::Range;
 use core::slice::SliceIndex;
 use types::*;
 
@@ -34,6 +35,11 @@ impl<'a> Elf64<'a> {
             && elf_header.e_ident[3] == Elf64Ehdr::ELFMAG3
     }
 
+    // ET_DYN: built position-independent, see relocate
+    pub fn is_position_independent(&self) -> Result<bool, &'static str> {
+        Ok(self.elf_header()?.e_type == Elf64Ehdr::ET_DYN)
+    }
+
     pub fn entry(&self) -> Result<u64, &'static str> {
         Ok(self.elf_header()?.e_entry)
     }
@@ -54,11 +60,11 @@ impl<'a> Elf64<'a> {
     pub fn program_headers(&self) -> Result<Elf64PhdrIter<'_>, &'static str> {
         let elf_header = self.elf_header()?;
 
-        let phdr_offset = elf_header.e_phoff as usize;
+        let phdr_offset = elf_header.e_phoff;
         let phdr_entsize = elf_header.e_phentsize as usize;
         let phdr_num = elf_header.e_phnum as usize;
 
-        let elf64_phdrs = self.get(phdr_offset..phdr_offset + phdr_entsize * phdr_num)?;
+        let elf64_phdrs = self.get(Self::range(phdr_offset, (phdr_entsize * phdr_num) as u64)?)?;
 
         Elf64PhdrIter::new(elf64_phdrs, phdr_num, phdr_entsize)
     }
@@ -83,15 +89,18 @@ impl<'a> Elf64<'a> {
             match phdr.p_type {
                 Elf64Phdr::PT_NULL => (),
                 Elf64Phdr::PT_LOAD => {
-                    if phdr.p_memsz < phdr.p_filesz
-                        || (self.bin.len() as u64) < phdr.p_offset + phdr.p_filesz
-                    {
+                    let in_file = Self::range(phdr.p_offset, phdr.p_filesz)
+                        .is_ok_and(|range| self.bin.get(range).is_some());
+                    let Some(mem_end) = phdr.p_vaddr.checked_add(phdr.p_memsz) else {
+                        return Err("program header is corrupted");
+                    };
+                    if phdr.p_memsz < phdr.p_filesz || !in_file {
                         return Err("program header is corrupted");
                     }
 
                     if flag {
                         lower_addr = phdr.p_vaddr;
-                        upper_addr = phdr.p_vaddr + phdr.p_memsz;
+                        upper_addr = mem_end;
                         flag = false;
                         continue;
                     }
@@ -99,8 +108,8 @@ impl<'a> Elf64<'a> {
                     if phdr.p_vaddr < lower_addr {
                         lower_addr = phdr.p_vaddr;
                     }
-                    if upper_addr < phdr.p_vaddr + phdr.p_memsz {
-                        upper_addr = phdr.p_vaddr + phdr.p_memsz;
+                    if upper_addr < mem_end {
+                        upper_addr = mem_end;
                     }
                 }
                 _ => (),
@@ -134,12 +143,15 @@ impl<'a> Elf64<'a> {
             match phdr.p_type {
                 Elf64Phdr::PT_NULL => (),
                 Elf64Phdr::PT_LOAD => {
-                    let file_offset = phdr.p_offset as usize;
-                    let file_offset_top = (phdr.p_offset + phdr.p_filesz) as usize;
-                    let load_src: &[u8] = &self.bin[file_offset..file_offset_top];
-                    let dst_offset = (phdr.p_vaddr - expand_base) as usize;
-                    let dst_offset_top = (phdr.p_vaddr + phdr.p_filesz - expand_base) as usize;
-                    buff[dst_offset..dst_offset_top].copy_from_slice(load_src);
+                    let load_src = self.get(Self::range(phdr.p_offset, phdr.p_filesz)?)?;
+                    let dst_offset = phdr
+                        .p_vaddr
+                        .checked_sub(expand_base)
+                        .ok_or("segment is below the image")?;
+                    let Some(dst) = buff.get_mut(Self::range(dst_offset, phdr.p_filesz)?) else {
+                        return Err("segment is out of the buffer");
+                    };
+                    dst.copy_from_slice(load_src);
                 }
                 _ => (),
             }
@@ -147,6 +159,101 @@ impl<'a> Elf64<'a> {
 
         Ok(())
     }
+
+    // Applies the dynamic relocations to an image expanded by `expand`, so that it runs
+    // `slide` bytes above the addresses it was linked at. Only R_X86_64_RELATIVE is
+    // supported, which is all a static PIE needs.
+    pub fn relocate(&self, buff: &mut [u8], slide: u64) -> Result<(), &'static str> {
+        let expand_base = self.expand_info()?.lower_addr;
+
+        for rela in self.relocations()? {
+            match rela.r_type() {
+                Elf64Rela::R_X86_64_NONE => (),
+                Elf64Rela::R_X86_64_RELATIVE => {
+                    let value = (rela.r_addend as u64).wrapping_add(slide);
+                    let Some(dst) = rela
+                        .r_offset
+                        .checked_sub(expand_base)
+                        .and_then(|offset| Self::range(offset, size_of::<u64>() as u64).ok())
+                        .and_then(|range| buff.get_mut(range))
+                    else {
+                        return Err("relocation is out of the image");
+                    };
+                    dst.copy_from_slice(&value.to_le_bytes());
+                }
+                _ => return Err("unsupported relocation type"),
+            }
+        }
+
+        Ok(())
+    }
+
+    // entries of DT_RELA, empty if the file has no dynamic section
+    pub fn relocations(&self) -> Result<Elf64RelaIter<'_>, &'static str> {
+        let Some(dynamic) = self
+            .program_headers()?
+            .find(|phdr| phdr.p_type == Elf64Phdr::PT_DYNAMIC)
+        else {
+            return Ok(Elf64RelaIter::empty());
+        };
+
+        let mut rela_addr = None;
+        let mut rela_size = 0;
+        let mut rela_entsize = size_of::<Elf64Rela>();
+        let dynamic_bin = self.get(Self::range(dynamic.p_offset, dynamic.p_filesz)?)?;
+        for entry in dynamic_bin.chunks_exact(size_of::<Elf64Dyn>()) {
+            let mut dyn_entry: Elf64Dyn = unsafe { mem::zeroed() };
+            unsafe {
+                entry
+                    .as_ptr()
+                    .copy_to(&mut dyn_entry as *mut _ as *mut u8, size_of::<Elf64Dyn>());
+            }
+            match dyn_entry.d_tag {
+                Elf64Dyn::DT_NULL => break,
+                Elf64Dyn::DT_RELA => rela_addr = Some(dyn_entry.d_val),
+                Elf64Dyn::DT_RELASZ => rela_size = dyn_entry.d_val as usize,
+                Elf64Dyn::DT_RELAENT => rela_entsize = dyn_entry.d_val as usize,
+                _ => (),
+            }
+        }
+
+        let Some(rela_addr) = rela_addr else {
+            return Ok(Elf64RelaIter::empty());
+        };
+        if rela_entsize < size_of::<Elf64Rela>() || rela_size % rela_entsize != 0 {
+            return Err("invalid relocation table");
+        }
+        let rela_offset = self.file_offset(rela_addr)?;
+        let rela_bin = self.get(Self::range(rela_offset as u64, rela_size as u64)?)?;
+
+        Ok(Elf64RelaIter {
+            bin: rela_bin,
+            entsize: rela_entsize,
+        })
+    }
+
+    // file offset of the virtual address `addr` inside a PT_LOAD segment
+    fn file_offset(&self, addr: u64) -> Result<usize, &'static str> {
+        self.program_headers()?
+            .find(|phdr| {
+                phdr.p_type == Elf64Phdr::PT_LOAD
+                    && phdr.p_vaddr <= addr
+                    && addr - phdr.p_vaddr < phdr.p_filesz
+            })
+            .and_then(|phdr| phdr.p_offset.checked_add(addr - phdr.p_vaddr))
+            .and_then(|offset| usize::try_from(offset).ok())
+            .ok_or("address is not in any segment")
+    }
+
+    // `size` bytes from `offset`, Err if that does not fit in the address space
+    fn range(offset: u64, size: u64) -> Result<Range<usize>, &'static str> {
+        let start = usize::try_from(offset).map_err(|_| "offset is out of range")?;
+        let end = offset
+            .checked_add(size)
+            .and_then(|end| usize::try_from(end).ok())
+            .ok_or("offset is out of range")?;
+        Ok(start..end)
+    }
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -233,6 +340,7 @@ impl Elf64Ehdr {
     pub const ELFOSABI_LINUX: u8 = 3;
 
     pub const ET_EXEC: Elf64Half = 2;
+    pub const ET_DYN: Elf64Half = 3;
     pub const EM_X86_64: Elf64Half = 62;
 }
 
@@ -252,6 +360,31 @@ pub struct Elf64Phdr {
 impl Elf64Phdr {
     pub const PT_NULL: Elf64Word = 0;
     pub const PT_LOAD: Elf64Word = 1;
+    pub const PT_DYNAMIC: Elf64Word = 2;
+
+    pub fn p_type(&self) -> Elf64Word {
+        self.p_type
+    }
+
+    pub fn p_offset(&self) -> Elf64Off {
+        self.p_offset
+    }
+
+    pub fn p_vaddr(&self) -> Elf64Addr {
+        self.p_vaddr
+    }
+
+    pub fn p_paddr(&self) -> Elf64Addr {
+        self.p_paddr
+    }
+
+    pub fn p_filesz(&self) -> Elf64Xword {
+        self.p_filesz
+    }
+
+    pub fn p_memsz(&self) -> Elf64Xword {
+        self.p_memsz
+    }
 
     pub fn x_flag(&self) -> bool {
         self.p_flags & 0x1 != 0
@@ -265,3 +398,286 @@ impl Elf64Phdr {
         self.p_flags & 0x4 != 0
     }
 }
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Elf64Dyn {
+    d_tag: Elf64Sxword,
+    d_val: Elf64Xword,
+}
+
+impl Elf64Dyn {
+    pub const DT_NULL: Elf64Sxword = 0;
+    pub const DT_RELA: Elf64Sxword = 7;
+    pub const DT_RELASZ: Elf64Sxword = 8;
+    pub const DT_RELAENT: Elf64Sxword = 9;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct Elf64Rela {
+    r_offset: Elf64Addr,
+    r_info: Elf64Xword,
+    r_addend: Elf64Sxword,
+}
+
+impl Elf64Rela {
+    pub const R_X86_64_NONE: Elf64Word = 0;
+    pub const R_X86_64_RELATIVE: Elf64Word = 8;
+
+    pub fn r_offset(&self) -> Elf64Addr {
+        self.r_offset
+    }
+
+    pub fn r_type(&self) -> Elf64Word {
+        self.r_info as Elf64Word
+    }
+
+    pub fn r_addend(&self) -> Elf64Sxword {
+        self.r_addend
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Elf64RelaIter<'a> {
+    bin: &'a [u8],
+    entsize: usize,
+}
+
+impl<'a> Elf64RelaIter<'a> {
+    fn empty() -> Self {
+        Self {
+            bin: &[],
+            entsize: size_of::<Elf64Rela>(),
+        }
+    }
+}
+
+impl<'a> Iterator for Elf64RelaIter<'a> {
+    type Item = Elf64Rela;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.entsize <= self.bin.len() {
+            let ptr: *const u8 = self.bin.as_ptr();
+            let mut rela: Elf64Rela = unsafe { mem::zeroed() };
+            unsafe {
+                ptr.copy_to(&mut rela as *mut _ as *mut u8, size_of::<Elf64Rela>());
+            }
+            self.bin = &self.bin[self.entsize..];
+
+            Some(rela)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec;
+    use std::vec::Vec;
+
+    // A static PIE: one PT_LOAD mapping the first FILE_SIZE bytes of the file at
+    // LINK_ADDR, with MEM_SIZE - FILE_SIZE bytes of bss, and a PT_DYNAMIC inside it.
+    const LINK_ADDR: u64 = 0x1000;
+    const FILE_SIZE: u64 = 0x200;
+    const MEM_SIZE: u64 = 0x300;
+    const PHDR_OFFSET: usize = 64;
+    const LOAD_PHDR: usize = PHDR_OFFSET;
+    const DYNAMIC_OFFSET: usize = 0x100;
+    const RELA_OFFSET: usize = 0x140;
+    const DATA_OFFSET: usize = 0x1c0;
+
+    fn put(bin: &mut [u8], offset: usize, bytes: &[u8]) {
+        bin[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn pie(relas: &[(u64, u32, i64)]) -> Vec<u8> {
+        let mut bin = vec![0; FILE_SIZE as usize];
+        put(&mut bin, 0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        put(&mut bin, 16, &Elf64Ehdr::ET_DYN.to_le_bytes());
+        put(&mut bin, 18, &Elf64Ehdr::EM_X86_64.to_le_bytes());
+        put(&mut bin, 32, &(PHDR_OFFSET as u64).to_le_bytes());
+        put(&mut bin, 54, &(size_of::<Elf64Phdr>() as u16).to_le_bytes());
+        put(&mut bin, 56, &2u16.to_le_bytes());
+
+        let phdrs = [
+            (Elf64Phdr::PT_LOAD, 0, LINK_ADDR, FILE_SIZE, MEM_SIZE),
+            (
+                Elf64Phdr::PT_DYNAMIC,
+                DYNAMIC_OFFSET as u64,
+                LINK_ADDR + DYNAMIC_OFFSET as u64,
+                64,
+                64,
+            ),
+        ];
+        for (index, (p_type, p_offset, p_vaddr, p_filesz, p_memsz)) in phdrs.iter().enumerate() {
+            let phdr = PHDR_OFFSET + index * size_of::<Elf64Phdr>();
+            put(&mut bin, phdr, &p_type.to_le_bytes());
+            put(&mut bin, phdr + 4, &6u32.to_le_bytes());
+            put(&mut bin, phdr + 8, &p_offset.to_le_bytes());
+            put(&mut bin, phdr + 16, &p_vaddr.to_le_bytes());
+            put(&mut bin, phdr + 24, &p_vaddr.to_le_bytes());
+            put(&mut bin, phdr + 32, &p_filesz.to_le_bytes());
+            put(&mut bin, phdr + 40, &p_memsz.to_le_bytes());
+        }
+
+        let dynamic = [
+            (Elf64Dyn::DT_RELA, LINK_ADDR + RELA_OFFSET as u64),
+            (
+                Elf64Dyn::DT_RELASZ,
+                (relas.len() * size_of::<Elf64Rela>()) as u64,
+            ),
+            (Elf64Dyn::DT_RELAENT, size_of::<Elf64Rela>() as u64),
+            (Elf64Dyn::DT_NULL, 0),
+        ];
+        for (index, (d_tag, d_val)) in dynamic.iter().enumerate() {
+            let entry = DYNAMIC_OFFSET + index * size_of::<Elf64Dyn>();
+            put(&mut bin, entry, &d_tag.to_le_bytes());
+            put(&mut bin, entry + 8, &d_val.to_le_bytes());
+        }
+
+        for (index, (r_offset, r_type, r_addend)) in relas.iter().enumerate() {
+            let rela = RELA_OFFSET + index * size_of::<Elf64Rela>();
+            put(&mut bin, rela, &r_offset.to_le_bytes());
+            put(&mut bin, rela + 8, &(*r_type as u64).to_le_bytes());
+            put(&mut bin, rela + 16, &r_addend.to_le_bytes());
+        }
+        bin
+    }
+
+    fn expanded(bin: &[u8]) -> Vec<u8> {
+        let mut buff = vec![0xff; MEM_SIZE as usize];
+        Elf64::new(bin).unwrap().expand(&mut buff).unwrap();
+        buff
+    }
+
+    #[test]
+    fn expand_copies_the_file_and_clears_bss() {
+        let mut bin = pie(&[]);
+        put(&mut bin, DATA_OFFSET, b"data");
+        let elf64 = Elf64::new(&bin).unwrap();
+        let expand_info = elf64.expand_info().unwrap();
+        assert_eq!(expand_info.lower_addr, LINK_ADDR);
+        assert_eq!(expand_info.upper_addr, LINK_ADDR + MEM_SIZE);
+
+        let buff = expanded(&bin);
+        assert_eq!(&buff[..FILE_SIZE as usize], &bin[..]);
+        assert!(buff[FILE_SIZE as usize..].iter().all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn expand_rejects_a_small_buffer() {
+        let bin = pie(&[]);
+        let mut buff = vec![0; MEM_SIZE as usize - 1];
+        assert_eq!(
+            Elf64::new(&bin).unwrap().expand(&mut buff),
+            Err("too small buffer size")
+        );
+    }
+
+    #[test]
+    fn segment_offset_overflow_is_rejected() {
+        let mut bin = pie(&[]);
+        put(&mut bin, LOAD_PHDR + 8, &(u64::MAX - 8).to_le_bytes());
+        let elf64 = Elf64::new(&bin).unwrap();
+        assert_eq!(
+            elf64.expand_info().unwrap_err(),
+            "program header is corrupted"
+        );
+        let mut buff = vec![0; MEM_SIZE as usize];
+        assert_eq!(elf64.expand(&mut buff), Err("program header is corrupted"));
+    }
+
+    #[test]
+    fn segment_address_overflow_is_rejected() {
+        let mut bin = pie(&[]);
+        put(&mut bin, LOAD_PHDR + 16, &(u64::MAX - 8).to_le_bytes());
+        assert_eq!(
+            Elf64::new(&bin).unwrap().expand_info().unwrap_err(),
+            "program header is corrupted"
+        );
+    }
+
+    #[test]
+    fn program_headers_out_of_file_are_rejected() {
+        let mut bin = pie(&[]);
+        put(&mut bin, 32, &u64::MAX.to_le_bytes());
+        assert!(Elf64::new(&bin).unwrap().program_headers().is_err());
+    }
+
+    #[test]
+    fn relocate_applies_relative_relocations() {
+        let bin = pie(&[
+            (
+                LINK_ADDR + DATA_OFFSET as u64,
+                Elf64Rela::R_X86_64_RELATIVE,
+                0x1234,
+            ),
+            (0, Elf64Rela::R_X86_64_NONE, 0),
+        ]);
+        let elf64 = Elf64::new(&bin).unwrap();
+        assert_eq!(elf64.relocations().unwrap().count(), 2);
+
+        let mut buff = expanded(&bin);
+        elf64.relocate(&mut buff, 0x10_0000).unwrap();
+        assert_eq!(
+            &buff[DATA_OFFSET..DATA_OFFSET + 8],
+            &0x10_1234u64.to_le_bytes()
+        );
+    }
+
+    #[test]
+    fn relocation_out_of_the_image_is_rejected() {
+        // the last 8 bytes of the image still fit, one past them do not
+        let last = LINK_ADDR + MEM_SIZE - 8;
+        for r_offset in [last + 1, LINK_ADDR - 8, u64::MAX - 4] {
+            let bin = pie(&[(r_offset, Elf64Rela::R_X86_64_RELATIVE, 0)]);
+            let mut buff = expanded(&bin);
+            assert_eq!(
+                Elf64::new(&bin).unwrap().relocate(&mut buff, 0),
+                Err("relocation is out of the image"),
+                "r_offset {:#x}",
+                r_offset
+            );
+        }
+
+        let bin = pie(&[(last, Elf64Rela::R_X86_64_RELATIVE, 0)]);
+        let mut buff = expanded(&bin);
+        assert_eq!(Elf64::new(&bin).unwrap().relocate(&mut buff, 0), Ok(()));
+    }
+
+    #[test]
+    fn unsupported_relocation_type_is_rejected() {
+        // R_X86_64_64 needs a symbol table
+        let bin = pie(&[(LINK_ADDR + DATA_OFFSET as u64, 1, 0)]);
+        let mut buff = expanded(&bin);
+        assert_eq!(
+            Elf64::new(&bin).unwrap().relocate(&mut buff, 0),
+            Err("unsupported relocation type")
+        );
+    }
+
+    #[test]
+    fn relocation_table_outside_the_segments_is_rejected() {
+        let mut bin = pie(&[(LINK_ADDR, Elf64Rela::R_X86_64_RELATIVE, 0)]);
+        put(
+            &mut bin,
+            DYNAMIC_OFFSET + 8,
+            &(LINK_ADDR + FILE_SIZE).to_le_bytes(),
+        );
+        assert_eq!(
+            Elf64::new(&bin).unwrap().relocations().unwrap_err(),
+            "address is not in any segment"
+        );
+        assert_eq!(
+            Elf64::new(&bin)
+                .unwrap()
+                .file_offset(LINK_ADDR + RELA_OFFSET as u64),
+            Ok(RELA_OFFSET)
+        );
+    }
+}
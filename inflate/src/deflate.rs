@@ -0,0 +1,431 @@
+// RFC 1951 raw deflate stream, decoded the canonical-Huffman way of zlib's puff.c.
+// Back references are resolved against `output` itself, so no window is needed.
+
+const MAX_BITS: usize = 15;
+const MAX_LITERAL_CODES: usize = 288;
+const MAX_DISTANCE_CODES: usize = 30;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+// order in which code length code lengths are stored in a dynamic block header
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+// decompresses `input` into `output` and returns the number of bytes written
+pub fn inflate(input: &[u8], output: &mut [u8]) -> Result<usize, &'static str> {
+    let mut inflater = Inflater {
+        input: Bits::new(input),
+        output: output,
+        output_len: 0,
+    };
+
+    loop {
+        let last = inflater.input.bits(1)? == 1;
+        match inflater.input.bits(2)? {
+            0 => inflater.stored()?,
+            1 => inflater.fixed()?,
+            2 => inflater.dynamic()?,
+            _ => return Err("invalid deflate block type"),
+        }
+        if last {
+            break;
+        }
+    }
+
+    Ok(inflater.output_len)
+}
+
+struct Bits<'a> {
+    data: &'a [u8],
+    offset: usize,
+    buff: u64,
+    count: usize,
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data: data,
+            offset: 0,
+            buff: 0,
+            count: 0,
+        }
+    }
+
+    // next `count` bits (at most 32), least significant first
+    fn bits(&mut self, count: usize) -> Result<u32, &'static str> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.offset)
+                .ok_or("deflate stream is truncated")?;
+            self.buff |= (byte as u64) << self.count;
+            self.offset += 1;
+            self.count += 8;
+        }
+        let value = (self.buff & ((1 << count) - 1)) as u32;
+        self.buff >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    // drops the rest of the current byte and returns the following `len` bytes
+    fn aligned_bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        self.buff = 0;
+        self.count = 0;
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + len)
+            .ok_or("deflate stream is truncated")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+}
+
+struct Huffman {
+    // number of codes of each length
+    counts: [u16; MAX_BITS + 1],
+    // symbols ordered by code
+    symbols: [u16; MAX_LITERAL_CODES],
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, &'static str> {
+        let mut huffman = Self {
+            counts: [0; MAX_BITS + 1],
+            symbols: [0; MAX_LITERAL_CODES],
+        };
+        for &length in lengths {
+            huffman.counts[length as usize] += 1;
+        }
+
+        // over-subscribed code sets are invalid, incomplete ones are allowed
+        let mut left: i32 = 1;
+        for len in 1..=MAX_BITS {
+            left <<= 1;
+            left -= huffman.counts[len] as i32;
+            if left < 0 {
+                return Err("over-subscribed huffman code");
+            }
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 1];
+        for len in 1..MAX_BITS {
+            offsets[len + 1] = offsets[len] + huffman.counts[len];
+        }
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                huffman.symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Ok(huffman)
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, &'static str> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for len in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("invalid huffman code")
+    }
+}
+
+struct Inflater<'a, 'b> {
+    input: Bits<'a>,
+    output: &'b mut [u8],
+    output_len: usize,
+}
+
+impl Inflater<'_, '_> {
+    fn stored(&mut self) -> Result<(), &'static str> {
+        let header = self.input.aligned_bytes(4)?;
+        let len = u16::from_le_bytes([header[0], header[1]]);
+        let nlen = u16::from_le_bytes([header[2], header[3]]);
+        if len != !nlen {
+            return Err("stored block length is corrupted");
+        }
+
+        let bytes = self.input.aligned_bytes(len as usize)?;
+        let output = self
+            .output
+            .get_mut(self.output_len..self.output_len + bytes.len())
+            .ok_or("output buffer is too small")?;
+        output.copy_from_slice(bytes);
+        self.output_len += bytes.len();
+        Ok(())
+    }
+
+    fn fixed(&mut self) -> Result<(), &'static str> {
+        let mut lengths = [0u8; MAX_LITERAL_CODES];
+        lengths[..144].fill(8);
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        lengths[280..].fill(8);
+        let literals = Huffman::new(&lengths)?;
+        let distances = Huffman::new(&[5; MAX_DISTANCE_CODES])?;
+        self.codes(&literals, &distances)
+    }
+
+    fn dynamic(&mut self) -> Result<(), &'static str> {
+        let literal_count = self.input.bits(5)? as usize + 257;
+        let distance_count = self.input.bits(5)? as usize + 1;
+        let code_length_count = self.input.bits(4)? as usize + 4;
+        if MAX_LITERAL_CODES - 2 < literal_count || MAX_DISTANCE_CODES < distance_count {
+            return Err("too many codes in dynamic block");
+        }
+
+        let mut code_lengths = [0u8; 19];
+        for &index in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_lengths[index] = self.input.bits(3)? as u8;
+        }
+        let code_length_huffman = Huffman::new(&code_lengths)?;
+
+        // literal/length and distance code lengths form one run-length coded sequence
+        let mut lengths = [0u8; MAX_LITERAL_CODES + MAX_DISTANCE_CODES];
+        let total = literal_count + distance_count;
+        let mut index = 0;
+        while index < total {
+            let symbol = code_length_huffman.decode(&mut self.input)?;
+            let (length, repeat) = match symbol {
+                0..=15 => (symbol as u8, 1),
+                16 => {
+                    if index == 0 {
+                        return Err("repeat with no previous code length");
+                    }
+                    (lengths[index - 1], 3 + self.input.bits(2)? as usize)
+                }
+                17 => (0, 3 + self.input.bits(3)? as usize),
+                _ => (0, 11 + self.input.bits(7)? as usize),
+            };
+            if total < index + repeat {
+                return Err("too many code lengths in dynamic block");
+            }
+            lengths[index..index + repeat].fill(length);
+            index += repeat;
+        }
+        if lengths[END_OF_BLOCK as usize] == 0 {
+            return Err("dynamic block has no end-of-block code");
+        }
+
+        let literals = Huffman::new(&lengths[..literal_count])?;
+        let distances = Huffman::new(&lengths[literal_count..total])?;
+        self.codes(&literals, &distances)
+    }
+
+    fn codes(&mut self, literals: &Huffman, distances: &Huffman) -> Result<(), &'static str> {
+        loop {
+            let symbol = literals.decode(&mut self.input)?;
+            if symbol < END_OF_BLOCK {
+                let byte = self
+                    .output
+                    .get_mut(self.output_len)
+                    .ok_or("output buffer is too small")?;
+                *byte = symbol as u8;
+                self.output_len += 1;
+                continue;
+            }
+            if symbol == END_OF_BLOCK {
+                return Ok(());
+            }
+
+            let length_index = (symbol - 257) as usize;
+            if LENGTH_BASE.len() <= length_index {
+                return Err("invalid length code");
+            }
+            let length = LENGTH_BASE[length_index] as usize
+                + self.input.bits(LENGTH_EXTRA[length_index] as usize)? as usize;
+
+            let distance_index = distances.decode(&mut self.input)? as usize;
+            if DISTANCE_BASE.len() <= distance_index {
+                return Err("invalid distance code");
+            }
+            let distance = DISTANCE_BASE[distance_index] as usize
+                + self.input.bits(DISTANCE_EXTRA[distance_index] as usize)? as usize;
+
+            if self.output_len < distance {
+                return Err("distance is too far back");
+            }
+            if self.output.len() < self.output_len + length {
+                return Err("output buffer is too small");
+            }
+            // the copy may overlap itself, e.g. distance 1 repeats the last byte
+            let from = self.output_len - distance;
+            for i in 0..length {
+                self.output[self.output_len + i] = self.output[from + i];
+            }
+            self.output_len += length;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+    use std::vec;
+    use std::vec::Vec;
+
+    const STORED: u8 = 0;
+    const FIXED: u8 = 1;
+    const DYNAMIC: u8 = 2;
+
+    fn deflate(data: &[u8], level: u32) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(level));
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // BTYPE of the first block
+    fn block_type(stream: &[u8]) -> u8 {
+        (stream[0] >> 1) & 0b11
+    }
+
+    fn round_trip(data: &[u8], stream: &[u8]) {
+        let mut output = vec![0u8; data.len()];
+        assert_eq!(inflate(stream, &mut output), Ok(data.len()));
+        assert_eq!(output, data);
+    }
+
+    // xorshift, deterministic and incompressible enough to force long streams
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state: u32 = 0x1234_5678;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn text() -> Vec<u8> {
+        let mut text = Vec::new();
+        for i in 0..2000 {
+            writeln!(
+                text,
+                "line {} of the kernel image, {} bytes so far",
+                i,
+                text.len()
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    #[test]
+    fn stored_blocks() {
+        // more than one 64KiB stored block
+        let data = noise(100_000);
+        let stream = deflate(&data, 0);
+        assert_eq!(block_type(&stream), STORED);
+        round_trip(&data, &stream);
+    }
+
+    #[test]
+    fn fixed_block() {
+        let data = b"abcabcabcabc hello hello hello";
+        let stream = deflate(data, 6);
+        assert_eq!(block_type(&stream), FIXED);
+        round_trip(data, &stream);
+    }
+
+    #[test]
+    fn dynamic_blocks() {
+        let data = text();
+        for level in [1, 6, 9] {
+            let stream = deflate(&data, level);
+            assert_eq!(block_type(&stream), DYNAMIC);
+            round_trip(&data, &stream);
+        }
+    }
+
+    #[test]
+    fn long_runs() {
+        // distance 1 copies that overlap themselves
+        let mut data = vec![0u8; 300_000];
+        data.extend(noise(1000));
+        data.extend(vec![0xffu8; 70_000]);
+        round_trip(&data, &deflate(&data, 9));
+    }
+
+    #[test]
+    fn truncated_stream() {
+        let data = text();
+        let stream = deflate(&data, 9);
+        let mut output = vec![0u8; data.len()];
+        assert_eq!(
+            inflate(&stream[..stream.len() / 2], &mut output),
+            Err("deflate stream is truncated")
+        );
+    }
+
+    #[test]
+    fn output_too_small() {
+        let data = text();
+        let stream = deflate(&data, 9);
+        let mut output = vec![0u8; data.len() - 1];
+        assert_eq!(
+            inflate(&stream, &mut output),
+            Err("output buffer is too small")
+        );
+    }
+
+    #[test]
+    fn over_subscribed_code() {
+        // final dynamic block, 257 literal and 1 distance code, then all 19 code length
+        // codes with length 1: far more codes than 1 bit can tell apart
+        let mut fields = vec![(1, 1), (DYNAMIC as u32, 2), (0, 5), (0, 5), (15, 4)];
+        fields.extend([(1, 3); 19]);
+
+        let mut stream = Vec::new();
+        let mut buff: u32 = 0;
+        let mut count = 0;
+        for (value, bits) in fields {
+            buff |= value << count;
+            count += bits;
+            while 8 <= count {
+                stream.push(buff as u8);
+                buff >>= 8;
+                count -= 8;
+            }
+        }
+        stream.push(buff as u8);
+
+        let mut output = [0u8; 16];
+        assert_eq!(
+            inflate(&stream, &mut output),
+            Err("over-subscribed huffman code")
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use crate::crc32;
+use crate::inflate;
+
+// RFC 1952 gzip member: header, raw deflate stream, then CRC-32 and size of the data.
+// Only the first member is read, which is what `gzip -9 kernel.elf` produces.
+#[derive(Clone, Copy, Debug)]
+pub struct Gzip<'a> {
+    deflate: &'a [u8],
+    crc32: u32,
+    size: u32,
+}
+
+impl<'a> Gzip<'a> {
+    const MAGIC: [u8; 2] = [0x1f, 0x8b];
+    const CM_DEFLATE: u8 = 8;
+
+    const FHCRC: u8 = 1 << 1;
+    const FEXTRA: u8 = 1 << 2;
+    const FNAME: u8 = 1 << 3;
+    const FCOMMENT: u8 = 1 << 4;
+    const FRESERVED: u8 = 0xe0;
+
+    const HEADER_SIZE: usize = 10;
+    const TRAILER_SIZE: usize = 8;
+
+    pub fn is_gzip(data: &[u8]) -> bool {
+        data.starts_with(&Self::MAGIC)
+    }
+
+    pub fn new(data: &'a [u8]) -> Result<Self, &'static str> {
+        if !Self::is_gzip(data) {
+            return Err("not a gzip file");
+        }
+        if data.len() < Self::HEADER_SIZE + Self::TRAILER_SIZE {
+            return Err("gzip file is truncated");
+        }
+        if data[2] != Self::CM_DEFLATE {
+            return Err("unsupported gzip compression method");
+        }
+        let flags = data[3];
+        if flags & Self::FRESERVED != 0 {
+            return Err("unsupported gzip flags");
+        }
+
+        let body = &data[..data.len() - Self::TRAILER_SIZE];
+        let mut offset = Self::HEADER_SIZE;
+        if flags & Self::FEXTRA != 0 {
+            let extra = body
+                .get(offset..offset + 2)
+                .ok_or("gzip header is truncated")?;
+            offset += 2 + u16::from_le_bytes([extra[0], extra[1]]) as usize;
+        }
+        if flags & Self::FNAME != 0 {
+            offset = Self::skip_string(body, offset)?;
+        }
+        if flags & Self::FCOMMENT != 0 {
+            offset = Self::skip_string(body, offset)?;
+        }
+        if flags & Self::FHCRC != 0 {
+            offset += 2;
+        }
+        let deflate = body.get(offset..).ok_or("gzip header is truncated")?;
+
+        let trailer = &data[data.len() - Self::TRAILER_SIZE..];
+        Ok(Self {
+            deflate: deflate,
+            crc32: u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]),
+            size: u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]),
+        })
+    }
+
+    // the trailer stores the size modulo 4GiB, plenty for a kernel
+    pub fn decompressed_size(&self) -> usize {
+        self.size as usize
+    }
+
+    pub fn decompress(&self, output: &mut [u8]) -> Result<usize, &'static str> {
+        let output = output
+            .get_mut(..self.decompressed_size())
+            .ok_or("output buffer is too small")?;
+        let size = inflate(self.deflate, output)?;
+        if size != output.len() {
+            return Err("gzip size does not match the data");
+        }
+        if crc32(output) != self.crc32 {
+            return Err("gzip crc32 does not match the data");
+        }
+        Ok(size)
+    }
+
+    fn skip_string(body: &[u8], offset: usize) -> Result<usize, &'static str> {
+        let string = body.get(offset..).ok_or("gzip header is truncated")?;
+        let len = string
+            .iter()
+            .position(|&byte| byte == 0)
+            .ok_or("gzip header is truncated")?;
+        Ok(offset + len + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use flate2::Compression;
+    use flate2::GzBuilder;
+    use std::io::Write;
+    use std::vec;
+    use std::vec::Vec;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        // a file name exercises the optional header fields
+        let mut encoder = GzBuilder::new()
+            .filename("kernel.elf")
+            .write(Vec::new(), Compression::best());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn data() -> Vec<u8> {
+        (0..50_000u32).map(|i| (i * i / 7) as u8).collect()
+    }
+
+    #[test]
+    fn decompress() {
+        let data = data();
+        let file = gzip(&data);
+        let gzip = Gzip::new(&file).unwrap();
+        let mut output = vec![0u8; gzip.decompressed_size()];
+        assert_eq!(gzip.decompress(&mut output), Ok(data.len()));
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn bad_crc() {
+        let data = data();
+        let mut file = gzip(&data);
+        let crc_offset = file.len() - 8;
+        file[crc_offset] ^= 1;
+
+        let gzip = Gzip::new(&file).unwrap();
+        let mut output = vec![0u8; gzip.decompressed_size()];
+        assert_eq!(
+            gzip.decompress(&mut output),
+            Err("gzip crc32 does not match the data")
+        );
+    }
+
+    #[test]
+    fn not_gzip() {
+        assert!(!Gzip::is_gzip(b"\x7fELF"));
+        assert!(Gzip::new(b"\x7fELF").is_err());
+        assert_eq!(
+            Gzip::new(&[0x1f, 0x8b, 8, 0]).err(),
+            Some("gzip file is truncated")
+        );
+    }
+}
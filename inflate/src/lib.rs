@@ -0,0 +1,12 @@
+#![no_std]
+
+// Decompression for kernel images. Output goes into a caller-provided buffer, so this
+// works before as-boot has a heap and after exit_boot_services alike.
+
+mod crc32;
+mod deflate;
+mod gzip;
+
+pub use crc32::crc32;
+pub use deflate::inflate;
+pub use gzip::Gzip;
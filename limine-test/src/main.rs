@@ -0,0 +1,399 @@
+#![no_main]
+#![no_std]
+
+#[macro_use]
+mod serial;
+
+use core::arch::asm;
+use core::panic::PanicInfo;
+use core::slice;
+use core::sync::atomic::AtomicU64;
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering;
+
+// A minimal Limine protocol kernel for `as-build test limine`. It asks for every request
+// as-boot answers, prints the responses on COM1, releases the APs and checks that they run,
+// then prints the same marker as as-kernel so that as-build can tell the boot succeeded.
+
+const COMMON_MAGIC: [u64; 2] = [0xc7b1dd30df4c8b88, 0x0a82e883a194f07b];
+const BASE_REVISION: u64 = 3;
+
+const MEMMAP_REQUEST: [u64; 2] = [0x67cf3d9d378a806f, 0xe304acdfc50c3c62];
+const FRAMEBUFFER_REQUEST: [u64; 2] = [0x9d5827dcd881dd75, 0xa3148604f6fab11b];
+const HHDM_REQUEST: [u64; 2] = [0x48dcf1cb8ad2b852, 0x63984e959a98244b];
+const RSDP_REQUEST: [u64; 2] = [0xc5e77b6b397e7b43, 0x27637845accdcf3c];
+const KERNEL_ADDRESS_REQUEST: [u64; 2] = [0x71ba76863cc55f63, 0xb2644a48c516a487];
+const MODULE_REQUEST: [u64; 2] = [0x3e7e279702be32af, 0xca1c4f3bd1280cee];
+const MP_REQUEST: [u64; 2] = [0x95a67b819a1b857e, 0xa0b61b723b6a73e0];
+
+const MEMMAP_USABLE: u64 = 0;
+// linker.ld
+const KERNEL_VIRTUAL_BASE: u64 = 0xffff_ffff_8000_0000;
+// iterations of `pause` the BSP waits for the APs, a few seconds even without KVM
+const AP_TIMEOUT_SPINS: usize = 500_000_000;
+
+#[used]
+#[unsafe(link_section = ".requests")]
+static BASE_REVISION_TAG: [AtomicU64; 3] = [
+    AtomicU64::new(0xf9562b2d5c95a6c8),
+    AtomicU64::new(0x6a7b384944536bdc),
+    AtomicU64::new(BASE_REVISION),
+];
+
+#[used]
+#[unsafe(link_section = ".requests")]
+static MEMMAP: Request = Request::new(MEMMAP_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static FRAMEBUFFER: Request = Request::new(FRAMEBUFFER_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static HHDM: Request = Request::new(HHDM_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static RSDP: Request = Request::new(RSDP_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static KERNEL_ADDRESS: Request = Request::new(KERNEL_ADDRESS_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static MODULE: Request = Request::new(MODULE_REQUEST);
+#[used]
+#[unsafe(link_section = ".requests")]
+static MP: Request = Request::new(MP_REQUEST);
+
+static APS_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+#[derive(Debug)]
+struct Request {
+    id: [u64; 4],
+    revision: u64,
+    response: AtomicU64,
+}
+
+impl Request {
+    const fn new(id: [u64; 2]) -> Self {
+        Self {
+            id: [COMMON_MAGIC[0], COMMON_MAGIC[1], id[0], id[1]],
+            revision: 0,
+            response: AtomicU64::new(0),
+        }
+    }
+
+    fn response<T>(&self) -> Option<&'static T> {
+        let response = self.response.load(Ordering::Acquire) as usize as *const T;
+        unsafe { response.as_ref() }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MemmapResponse {
+    revision: u64,
+    entry_count: u64,
+    entries: *const *const MemmapEntry,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MemmapEntry {
+    base: u64,
+    length: u64,
+    kind: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct FramebufferResponse {
+    revision: u64,
+    framebuffer_count: u64,
+    framebuffers: *const *const Framebuffer,
+}
+
+// only the fields up to the masks, the rest is not read
+#[repr(C)]
+#[derive(Debug)]
+struct Framebuffer {
+    address: u64,
+    width: u64,
+    height: u64,
+    pitch: u64,
+    bpp: u16,
+    memory_model: u8,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct HhdmResponse {
+    revision: u64,
+    offset: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct RsdpResponse {
+    revision: u64,
+    address: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct KernelAddressResponse {
+    revision: u64,
+    physical_base: u64,
+    virtual_base: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct ModuleResponse {
+    revision: u64,
+    module_count: u64,
+    modules: *const *const ModuleFile,
+}
+
+// only the fields up to `path`, the rest is not read
+#[repr(C)]
+#[derive(Debug)]
+struct ModuleFile {
+    revision: u64,
+    address: u64,
+    size: u64,
+    path: *const u8,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MpResponse {
+    revision: u64,
+    flags: u32,
+    bsp_lapic_id: u32,
+    cpu_count: u64,
+    cpus: *const *const MpInfo,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+struct MpInfo {
+    processor_id: u32,
+    lapic_id: u32,
+    reserved: u64,
+    goto_address: AtomicU64,
+    extra_argument: u64,
+}
+
+#[unsafe(no_mangle)]
+pub extern "sysv64" fn _start() -> ! {
+    println!(
+        "LIMINE-TEST: entered at {:#x}",
+        _start as *const () as usize
+    );
+
+    let mut failures = 0;
+    let mut check = |ok: bool, what: &str| {
+        if !ok {
+            println!("LIMINE-TEST: FAILED: {}", what);
+            failures += 1;
+        }
+    };
+
+    // the bootloader clears the last word of the tag when it supports the revision
+    let base_revision_supported = BASE_REVISION_TAG[2].load(Ordering::Acquire) == 0;
+    println!(
+        "LIMINE-TEST: base revision {} supported: {}",
+        BASE_REVISION, base_revision_supported
+    );
+    check(base_revision_supported, "base revision");
+
+    let hhdm_offset = match HHDM.response::<HhdmResponse>() {
+        Some(hhdm) => {
+            println!("LIMINE-TEST: hhdm offset {:#x}", hhdm.offset);
+            hhdm.offset
+        }
+        None => 0,
+    };
+    check(hhdm_offset != 0, "hhdm response");
+
+    match MEMMAP.response::<MemmapResponse>() {
+        Some(memmap) => {
+            let entries =
+                unsafe { slice::from_raw_parts(memmap.entries, memmap.entry_count as usize) };
+            let mut usable = 0;
+            let mut sorted = true;
+            let mut end = 0;
+            for &entry in entries {
+                let entry = unsafe { &*entry };
+                println!(
+                    "LIMINE-TEST: memmap {:#012x}-{:#012x} type {}",
+                    entry.base,
+                    entry.base + entry.length,
+                    entry.kind
+                );
+                sorted &= end <= entry.base;
+                end = entry.base + entry.length;
+                if entry.kind == MEMMAP_USABLE {
+                    usable += entry.length;
+                }
+            }
+            println!(
+                "LIMINE-TEST: memmap {} entries, {} KiB usable",
+                entries.len(),
+                usable / 1024
+            );
+            check(sorted, "memmap entries are sorted and disjoint");
+            check(usable != 0, "memmap has usable memory");
+        }
+        None => check(false, "memmap response"),
+    }
+
+    match FRAMEBUFFER.response::<FramebufferResponse>() {
+        Some(response) if response.framebuffer_count != 0 => {
+            let framebuffer = unsafe { &**response.framebuffers };
+            println!(
+                "LIMINE-TEST: framebuffer {:#x} {}x{} pitch {} bpp {} model {}",
+                framebuffer.address,
+                framebuffer.width,
+                framebuffer.height,
+                framebuffer.pitch,
+                framebuffer.bpp,
+                framebuffer.memory_model
+            );
+            check(framebuffer.bpp == 32, "framebuffer is 32bpp");
+            // a white line across the top proves the framebuffer is mapped
+            let line = unsafe {
+                slice::from_raw_parts_mut(
+                    framebuffer.address as usize as *mut u32,
+                    framebuffer.width as usize,
+                )
+            };
+            line.fill(0x00ff_ffff);
+        }
+        _ => check(false, "framebuffer response"),
+    }
+
+    match RSDP.response::<RsdpResponse>() {
+        Some(rsdp) => {
+            println!("LIMINE-TEST: rsdp {:#x}", rsdp.address);
+            // physical from base revision 3 on
+            let signature = unsafe {
+                slice::from_raw_parts((hhdm_offset + rsdp.address) as usize as *const u8, 8)
+            };
+            check(signature == b"RSD PTR ", "rsdp signature");
+        }
+        None => check(false, "rsdp response"),
+    }
+
+    match KERNEL_ADDRESS.response::<KernelAddressResponse>() {
+        Some(kernel_address) => {
+            println!(
+                "LIMINE-TEST: kernel physical {:#x} virtual {:#x}",
+                kernel_address.physical_base, kernel_address.virtual_base
+            );
+            check(
+                kernel_address.virtual_base == KERNEL_VIRTUAL_BASE,
+                "kernel virtual base",
+            );
+            check(
+                kernel_address.physical_base != 0 && kernel_address.physical_base % 4096 == 0,
+                "kernel physical base",
+            );
+        }
+        None => check(false, "kernel address response"),
+    }
+
+    // boot.cfg may not list any, an empty response is fine
+    match MODULE.response::<ModuleResponse>() {
+        Some(response) => {
+            println!("LIMINE-TEST: {} modules", response.module_count);
+            let modules =
+                unsafe { slice::from_raw_parts(response.modules, response.module_count as usize) };
+            for &module in modules {
+                let module = unsafe { &*module };
+                println!(
+                    "LIMINE-TEST: module {} at {:#x}, {} bytes",
+                    c_str(module.path),
+                    module.address,
+                    module.size
+                );
+            }
+        }
+        None => check(false, "module response"),
+    }
+
+    match MP.response::<MpResponse>() {
+        Some(mp) => {
+            let cpus = unsafe { slice::from_raw_parts(mp.cpus, mp.cpu_count as usize) };
+            println!(
+                "LIMINE-TEST: {} CPUs, BSP local APIC {}, flags {:#x}",
+                cpus.len(),
+                mp.bsp_lapic_id,
+                mp.flags
+            );
+            let mut aps = 0;
+            for &cpu in cpus {
+                let cpu = unsafe { &*cpu };
+                println!(
+                    "LIMINE-TEST: CPU {} local APIC {}",
+                    cpu.processor_id, cpu.lapic_id
+                );
+                if cpu.lapic_id != mp.bsp_lapic_id {
+                    cpu.goto_address
+                        .store(ap_main as *const () as u64, Ordering::Release);
+                    aps += 1;
+                }
+            }
+
+            let mut spins = 0;
+            while APS_RUNNING.load(Ordering::Acquire) < aps && spins < AP_TIMEOUT_SPINS {
+                core::hint::spin_loop();
+                spins += 1;
+            }
+            let running = APS_RUNNING.load(Ordering::Acquire);
+            println!("LIMINE-TEST: {} of {} APs running", running, aps);
+            check(running == aps, "every AP reached its goto_address");
+        }
+        None => check(false, "mp response"),
+    }
+
+    if failures == 0 {
+        println!("LIMINE-TEST: all responses are valid");
+        // as-build test waits for this line
+        println!("KERNEL: booted");
+    } else {
+        println!("LIMINE-TEST: {} checks failed", failures);
+    }
+    halt()
+}
+
+extern "sysv64" fn ap_main(info: &MpInfo) -> ! {
+    let _ = info;
+    APS_RUNNING.fetch_add(1, Ordering::AcqRel);
+    halt()
+}
+
+fn c_str(ptr: *const u8) -> &'static str {
+    if ptr.is_null() {
+        return "";
+    }
+    let mut len = 0;
+    while unsafe { *ptr.add(len) } != 0 {
+        len += 1;
+    }
+    let bytes = unsafe { slice::from_raw_parts(ptr, len) };
+    core::str::from_utf8(bytes).unwrap_or("?")
+}
+
+fn halt() -> ! {
+    loop {
+        unsafe {
+            asm!("cli", "hlt");
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("LIMINE-TEST: panic: {}", info.message());
+    halt()
+}